@@ -0,0 +1,78 @@
+//! The unit trigonometric functions take and give angles in.
+
+use std::{
+    f64::consts::{PI, TAU},
+    fmt::Display,
+};
+
+use super::{number::Number, Num};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AngleMode {
+    #[default]
+    Radians,
+    Degrees,
+    Gradians,
+}
+
+impl AngleMode {
+    pub fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_lowercase().as_str() {
+            "rad" | "radians" => Self::Radians,
+            "deg" | "degrees" => Self::Degrees,
+            "grad" | "gradians" => Self::Gradians,
+            _ => return None,
+        })
+    }
+
+    /// Size of a full turn in this unit.
+    pub fn turn(self) -> Num {
+        match self {
+            Self::Radians => TAU,
+            Self::Degrees => 360.,
+            Self::Gradians => 400.,
+        }
+    }
+
+    /// Converts an angle in this unit to radians.
+    pub fn to_radians<N: Number>(self, x: N) -> N {
+        match self {
+            Self::Radians => x,
+            _ => x * N::constant(TAU / self.turn()),
+        }
+    }
+
+    /// Converts an angle in radians to this unit.
+    pub fn convert_radians<N: Number>(self, x: N) -> N {
+        match self {
+            Self::Radians => x,
+            _ => x * N::constant(self.turn() / TAU),
+        }
+    }
+
+    /// Converts an angle in degrees to this unit, which is what `30°` means.
+    pub fn convert_degrees<N: Number>(self, x: N) -> N {
+        match self {
+            Self::Degrees => x,
+            _ => self.convert_radians(x * N::constant(PI / 180.)),
+        }
+    }
+
+    /// Which quarter of a turn `x` lands exactly on, if any. Never for radians, as multiples of
+    /// `pi` can not be given exactly.
+    pub fn quarter_turns(self, x: Num) -> Option<usize> {
+        let quarters = x / (self.turn() / 4.);
+        (self != Self::Radians && quarters.is_finite() && quarters.fract() == 0.)
+            .then(|| quarters.rem_euclid(4.) as usize)
+    }
+}
+
+impl Display for AngleMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Radians => "rad",
+            Self::Degrees => "deg",
+            Self::Gradians => "grad",
+        })
+    }
+}
@@ -0,0 +1,105 @@
+//! Complex numbers, for results like the roots of polynomials.
+
+use std::{
+    fmt::Display,
+    ops::{Add, Div, Mul, Neg, Sub},
+};
+
+use super::Num;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Complex {
+    pub re: Num,
+    pub im: Num,
+}
+
+impl Complex {
+    pub fn new(re: Num, im: Num) -> Self {
+        Self { re, im }
+    }
+
+    pub fn from_polar(r: Num, theta: Num) -> Self {
+        Self::new(r * theta.cos(), r * theta.sin())
+    }
+
+    pub fn abs(&self) -> Num {
+        self.re.hypot(self.im)
+    }
+
+    pub fn arg(&self) -> Num {
+        self.im.atan2(self.re)
+    }
+
+    pub fn conj(&self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    /// Writes the number out like `Display`, but with both parts written by `number`.
+    pub fn to_string_with(self, number: &dyn Fn(Num) -> String) -> String {
+        match (self.re, self.im) {
+            (re, 0.) => number(re),
+            (0., im) => format!("{}i", number(im)),
+            (re, im) if im < 0. => format!("{} - {}i", number(re), number(-im)),
+            (re, im) => format!("{} + {}i", number(re), number(im)),
+        }
+    }
+}
+
+impl From<Num> for Complex {
+    fn from(re: Num) -> Self {
+        Self::new(re, 0.)
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        let den = rhs.re * rhs.re + rhs.im * rhs.im;
+        Self::new(
+            (self.re * rhs.re + self.im * rhs.im) / den,
+            (self.im * rhs.re - self.re * rhs.im) / den,
+        )
+    }
+}
+
+impl Neg for Complex {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.re, -self.im)
+    }
+}
+
+impl Display for Complex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_string_with(&|x| x.to_string()))
+    }
+}
@@ -0,0 +1,308 @@
+use std::{collections::BTreeMap, fmt::Display, ops::Range};
+
+use rand::Rng;
+
+use super::{Error, Result};
+
+// Most steps we are willing to take computing an exact distribution
+const MAX_DIST_COST: u64 = 100_000_000;
+// Most dice rolled at once, every roll is kept in memory
+const MAX_ROLL_COUNT: u32 = 1_000_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dice {
+    pub count: u32,
+    pub sides: u32,
+    pub modifier: Option<(Modifier, u32)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Modifier {
+    KeepHighest,
+    KeepLowest,
+    DropHighest,
+    DropLowest,
+}
+
+impl Dice {
+    /// Parses dice notation like `3d6`, `d%`, `2d20kh1` or `4d6dl1`.
+    /// Returns None if the input is not dice notation at all.
+    pub fn parse(inp: &str) -> Option<Dice> {
+        let (count, rest) = inp.split_once('d')?;
+        let count = match count {
+            "" => 1,
+            i => i.parse().ok()?,
+        };
+
+        let sides_end = rest
+            .find(|x: char| !x.is_ascii_digit() && x != '%')
+            .unwrap_or(rest.len());
+        let (sides, rest) = rest.split_at(sides_end);
+        let sides = match sides {
+            "%" => 100,
+            i => i.parse().ok()?,
+        };
+
+        let modifier = match rest.get(..2) {
+            None if rest.is_empty() => None,
+            Some(i) => Some((
+                match i {
+                    "kh" => Modifier::KeepHighest,
+                    "kl" => Modifier::KeepLowest,
+                    "dh" => Modifier::DropHighest,
+                    "dl" => Modifier::DropLowest,
+                    _ => return None,
+                },
+                rest[2..].parse().ok()?,
+            )),
+            None => return None,
+        };
+
+        Some(Dice {
+            count,
+            sides,
+            modifier,
+        })
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.count > 0 && self.sides > 0 && self.modifier.map(|x| x.1 <= self.count).unwrap_or(true)
+    }
+
+    /// Rolls every die, returning the raw (unsorted) results.
+    pub fn roll(&self, rng: &mut impl Rng) -> Result<Vec<u32>> {
+        if self.count > MAX_ROLL_COUNT {
+            return Err(Error::InvalidArgument(
+                self.to_string(),
+                format!("at most {} dice can be rolled at once", MAX_ROLL_COUNT),
+            ));
+        }
+
+        Ok((0..self.count)
+            .map(|_| rng.gen_range(1..=self.sides))
+            .collect())
+    }
+
+    /// Indexes of the dice that are kept out of the given rolls.
+    pub fn kept(&self, rolls: &[u32]) -> Vec<usize> {
+        let mut order = (0..rolls.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&x| rolls[x]);
+        order[self.kept_range()].to_vec()
+    }
+
+    // A million dice with four billion sides each still fit in a u64
+    pub fn total(&self, rolls: &[u32]) -> u64 {
+        self.kept(rolls).iter().map(|&x| rolls[x] as u64).sum()
+    }
+
+    /// Exact probability of every possible total.
+    pub fn distribution(&self) -> Result<BTreeMap<i64, f64>> {
+        if self.distribution_cost() > MAX_DIST_COST {
+            return Err(Error::InvalidArgument(
+                "dist".to_owned(),
+                format!("`{}` is too large to compute exactly", self),
+            ));
+        }
+
+        let range = self.kept_range();
+        if range.len() == self.count as usize {
+            return Ok(self.convolve_all());
+        }
+
+        // Keeping the lowest dice is the same as keeping the highest with the faces reversed
+        let highest = range.end == self.count as usize;
+        let faces = (1..=self.sides as i64).collect::<Vec<_>>();
+        let faces = match highest {
+            true => faces.into_iter().rev().collect::<Vec<_>>(),
+            false => faces,
+        };
+        Ok(self.order_statistic(&faces, range.len()))
+    }
+
+    // Rough number of steps `distribution` takes. Adding every die goes through each total so
+    // far for each face, while keeping some of them goes through every number of dice assigned
+    // and every kept total, for each face and each number of dice given that face.
+    fn distribution_cost(&self) -> u64 {
+        let count = self.count as u64;
+        let sides = self.sides as u64;
+        let keep = self.kept_range().len() as u64;
+        let squares = sides.saturating_mul(sides);
+        match keep == count {
+            true => (count * count.saturating_sub(1) / 2)
+                .saturating_mul(squares)
+                .saturating_add(count * sides),
+            false => (count * count / 2)
+                .saturating_mul(keep)
+                .saturating_mul(squares),
+        }
+    }
+
+    // Sorted ascending, the range of rolls that count towards the total
+    fn kept_range(&self) -> Range<usize> {
+        let count = self.count as usize;
+        match self.modifier {
+            None => 0..count,
+            Some((Modifier::KeepHighest, n)) => count - n as usize..count,
+            Some((Modifier::KeepLowest, n)) => 0..n as usize,
+            Some((Modifier::DropHighest, n)) => 0..count - n as usize,
+            Some((Modifier::DropLowest, n)) => n as usize..count,
+        }
+    }
+
+    fn convolve_all(&self) -> BTreeMap<i64, f64> {
+        let sides = self.sides as usize;
+        let p = 1. / sides as f64;
+
+        // dist[sum] after adding each die in turn
+        let mut dist = vec![1.];
+        for _ in 0..self.count {
+            let mut next = vec![0.; dist.len() + sides];
+            for (sum, &weight) in dist.iter().enumerate() {
+                for face in 1..=sides {
+                    next[sum + face] += weight * p;
+                }
+            }
+            dist = next;
+        }
+
+        dist.into_iter()
+            .enumerate()
+            .filter(|x| x.1 > 0.)
+            .map(|(sum, prob)| (sum as i64, prob))
+            .collect()
+    }
+
+    // Assigns dice to faces in the given order, only the first `keep` dice assigned count.
+    // dp[assigned][sum] is the probability of that partial assignment. A die not yet assigned is
+    // equally likely to show any of the faces left, so how many of them show the next face is
+    // binomial.
+    fn order_statistic(&self, faces: &[i64], keep: usize) -> BTreeMap<i64, f64> {
+        let count = self.count as usize;
+        let max_sum = keep * self.sides as usize;
+
+        // Logs of factorials, so binomial coefficients of many dice do not overflow
+        let mut ln_factorial = vec![0.; count + 1];
+        for n in 1..=count {
+            ln_factorial[n] = ln_factorial[n - 1] + (n as f64).ln();
+        }
+        let binomial = |n: usize, j: usize, q: f64| -> f64 {
+            match q {
+                1. => (j == n) as u8 as f64,
+                q => (ln_factorial[n] - ln_factorial[j] - ln_factorial[n - j]
+                    + j as f64 * q.ln()
+                    + (n - j) as f64 * (1. - q).ln())
+                .exp(),
+            }
+        };
+
+        let mut dp = vec![vec![0.; max_sum + 1]; count + 1];
+        dp[0][0] = 1.;
+        for (i, &face) in faces.iter().enumerate() {
+            let q = 1. / (faces.len() - i) as f64;
+            let mut next = vec![vec![0.; max_sum + 1]; count + 1];
+            for assigned in 0..=count {
+                let left = count - assigned;
+                let chances = (0..=left).map(|j| binomial(left, j, q)).collect::<Vec<_>>();
+                for (sum, &weight) in dp[assigned].iter().enumerate() {
+                    if weight == 0. {
+                        continue;
+                    }
+
+                    for (j, chance) in chances.iter().enumerate() {
+                        let kept = j.min(keep.saturating_sub(assigned));
+                        next[assigned + j][sum + kept * face as usize] += weight * chance;
+                    }
+                }
+            }
+            dp = next;
+        }
+
+        dp[count]
+            .iter()
+            .enumerate()
+            .filter(|x| *x.1 > 0.)
+            .map(|(sum, prob)| (sum as i64, *prob))
+            .collect()
+    }
+}
+
+/// Distribution of the sum of two independent distributions.
+pub fn convolve(a: &BTreeMap<i64, f64>, b: &BTreeMap<i64, f64>) -> BTreeMap<i64, f64> {
+    let mut out = BTreeMap::new();
+    for (av, ap) in a {
+        for (bv, bp) in b {
+            *out.entry(av + bv).or_insert(0.) += ap * bp;
+        }
+    }
+
+    out
+}
+
+impl Display for Dice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.sides {
+            100 => write!(f, "{}d%", self.count)?,
+            _ => write!(f, "{}d{}", self.count, self.sides)?,
+        }
+
+        if let Some((modifier, n)) = self.modifier {
+            let modifier = match modifier {
+                Modifier::KeepHighest => "kh",
+                Modifier::KeepLowest => "kl",
+                Modifier::DropHighest => "dh",
+                Modifier::DropLowest => "dl",
+            };
+            write!(f, "{}{}", modifier, n)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dist(inp: &str) -> BTreeMap<i64, f64> {
+        Dice::parse(inp).unwrap().distribution().unwrap()
+    }
+
+    fn mean(dist: &BTreeMap<i64, f64>) -> f64 {
+        dist.iter().map(|(x, p)| *x as f64 * p).sum()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() <= 1e-12,
+            "got {actual}, expected {expected}"
+        );
+    }
+
+    #[test]
+    fn test_distribution() {
+        let two = dist("2d6");
+        assert_eq!(two.len(), 11);
+        assert_close(two[&7], 6. / 36.);
+        assert_close(two[&2], 1. / 36.);
+        assert_close(mean(&two), 7.);
+        assert_close(two.values().sum(), 1.);
+    }
+
+    #[test]
+    fn test_kept_distribution() {
+        // Means of the highest and lowest dice over all 6^4 and 6^2 outcomes
+        let highest = dist("4d6kh3");
+        assert_close(mean(&highest), 15869. / 1296.);
+        assert_close(highest[&18], 21. / 1296.);
+        assert_close(highest.values().sum(), 1.);
+        assert_close(mean(&dist("2d20kl1")), 2870. / 400.);
+        assert_close(mean(&dist("4d6dl1")), mean(&highest));
+    }
+
+    #[test]
+    fn test_distribution_too_large() {
+        assert!(Dice::parse("50000d2kh1").unwrap().distribution().is_err());
+        assert!(Dice::parse("300d300kh1").unwrap().distribution().is_err());
+        assert!(Dice::parse("1d100000").unwrap().distribution().is_ok());
+    }
+}
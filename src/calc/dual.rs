@@ -0,0 +1,78 @@
+//! Dual numbers `re + eps ε` with `ε^2 = 0`. Evaluating an expression with `eps = 1` for one
+//! variable gives the derivative with respect to that variable in `eps`.
+
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
+
+use super::Num;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dual {
+    pub re: Num,
+    pub eps: Num,
+}
+
+impl Dual {
+    pub fn new(re: Num, eps: Num) -> Self {
+        Self { re, eps }
+    }
+
+    /// Applies a function with the given value and derivative at `re`, using the chain rule.
+    pub fn chain(self, value: Num, derivative: Num) -> Self {
+        Self::new(value, self.eps * derivative)
+    }
+}
+
+impl Add for Dual {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.re + rhs.re, self.eps + rhs.eps)
+    }
+}
+
+impl Sub for Dual {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.re - rhs.re, self.eps - rhs.eps)
+    }
+}
+
+impl Mul for Dual {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(self.re * rhs.re, self.re * rhs.eps + self.eps * rhs.re)
+    }
+}
+
+impl Div for Dual {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        Self::new(
+            self.re / rhs.re,
+            (self.eps * rhs.re - self.re * rhs.eps) / (rhs.re * rhs.re),
+        )
+    }
+}
+
+// a % b = a - b * trunc(a / b), where the truncated part is constant almost everywhere
+impl Rem for Dual {
+    type Output = Self;
+
+    fn rem(self, rhs: Self) -> Self {
+        Self::new(
+            self.re % rhs.re,
+            self.eps - rhs.eps * (self.re / rhs.re).trunc(),
+        )
+    }
+}
+
+impl Neg for Dual {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.re, -self.eps)
+    }
+}
@@ -0,0 +1,201 @@
+//! How numbers in results are written out.
+
+use std::fmt::Display;
+
+use super::{Num, Token};
+use crate::funcs::closed_form;
+
+// Significant digits kept by the smart notation, which hides rounding errors in the last ones
+const SMART_DIGITS: usize = 15;
+// Magnitudes the smart notation writes out in full, anything outside switches to scientific
+const SMART_MIN_EXP: i32 = -7;
+const SMART_MAX_EXP: i32 = 16;
+
+const SI_PREFIXES: [&str; 17] = [
+    "y", "z", "a", "f", "p", "n", "µ", "m", "", "k", "M", "G", "T", "P", "E", "Z", "Y",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Notation {
+    /// Shortest form after dropping float noise, like `0.3` for `0.1 + 0.2`
+    #[default]
+    Smart,
+    /// A fixed number of decimals
+    Fixed(usize),
+    /// A fixed number of significant figures
+    Sig(usize),
+    /// Scientific, like `4.7e3`
+    Sci,
+    /// Scientific with exponents that are multiples of three, like `47e3`
+    Eng,
+    /// Engineering with SI prefixes instead of exponents, like `47k`
+    Si,
+    /// Fractions and multiples of constants where they are exact, like `1 / 3` or `pi / 2`
+    Frac,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Format {
+    pub notation: Notation,
+    /// Group the digits before the decimal point in threes, like `1,000,000`
+    pub separators: bool,
+}
+
+impl Format {
+    pub fn number(&self, x: Num) -> String {
+        if !x.is_finite() {
+            return x.to_string();
+        }
+
+        let out = match self.notation {
+            Notation::Smart => smart(x),
+            Notation::Fixed(decimals) => fixed(x, decimals),
+            Notation::Sig(digits) => sig(x, digits),
+            Notation::Sci => format!("{:e}", trim(x)),
+            Notation::Eng => match engineering(x) {
+                (mantissa, 0) => mantissa,
+                (mantissa, exp) => format!("{}e{}", mantissa, exp),
+            },
+            Notation::Si => match engineering(x) {
+                (mantissa, exp) if exp.abs() <= 24 => {
+                    format!("{}{}", mantissa, SI_PREFIXES[(exp / 3 + 8) as usize])
+                }
+                (mantissa, exp) => format!("{}e{}", mantissa, exp),
+            },
+            Notation::Frac => match closed_form(x) {
+                Some(i) => i.to_string(),
+                None => smart(x),
+            },
+        };
+
+        match self.separators {
+            true => separate(&out),
+            false => out,
+        }
+    }
+
+    /// Writes out a result with every number in this format. Numbers written as expressions,
+    /// like `1 / 3`, are put in parentheses inside of other expressions.
+    pub fn token(&self, token: &Token) -> String {
+        match token {
+            Token::Number(x) => self.number(*x),
+            Token::List(items) => format!(
+                "[{}]",
+                items
+                    .iter()
+                    .map(|x| self.token(x))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            _ => token.to_string_with(&|x| match self.number(x) {
+                i if i.contains(' ') => format!("({})", i),
+                i => i,
+            }),
+        }
+    }
+}
+
+impl Display for Notation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Notation::Smart => write!(f, "smart"),
+            Notation::Fixed(i) => write!(f, "fixed {}", i),
+            Notation::Sig(i) => write!(f, "sig {}", i),
+            Notation::Sci => write!(f, "sci"),
+            Notation::Eng => write!(f, "eng"),
+            Notation::Si => write!(f, "si"),
+            Notation::Frac => write!(f, "frac"),
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.notation)?;
+        if self.separators {
+            write!(f, ", sep")?;
+        }
+        Ok(())
+    }
+}
+
+// Rounds off the last few digits, where float noise like `0.30000000000000004` shows up
+fn trim(x: Num) -> Num {
+    format!("{:.*e}", SMART_DIGITS - 1, x).parse().unwrap_or(x)
+}
+
+// Power of ten of the leading digit
+fn exponent(x: Num) -> i32 {
+    let sci = format!("{:e}", x);
+    sci[sci.find('e').unwrap() + 1..].parse().unwrap()
+}
+
+fn smart(x: Num) -> String {
+    // Whole numbers that are stored exactly are kept as they are
+    if x.fract() == 0. && x.abs() < 10_f64.powi(SMART_MAX_EXP) {
+        return x.to_string();
+    }
+
+    let x = trim(x);
+    match exponent(x) {
+        exp if x != 0. && !(SMART_MIN_EXP..SMART_MAX_EXP).contains(&exp) => format!("{:e}", x),
+        _ => x.to_string(),
+    }
+}
+
+fn fixed(x: Num, decimals: usize) -> String {
+    let out = format!("{:.*}", decimals, x);
+    // Small negatives round to zero, which has no sign
+    match out.strip_prefix('-') {
+        Some(i) if i.chars().all(|x| x == '0' || x == '.') => i.to_owned(),
+        _ => out,
+    }
+}
+
+fn sig(x: Num, digits: usize) -> String {
+    let sci = format!("{:.*e}", digits.max(1) - 1, x);
+    let rounded = sci.parse::<Num>().unwrap();
+    match exponent(rounded) {
+        exp if (SMART_MIN_EXP..SMART_MAX_EXP).contains(&exp) => {
+            let decimals = (digits as i32 - 1 - exp).max(0) as usize;
+            format!("{:.*}", decimals, rounded)
+        }
+        _ => sci,
+    }
+}
+
+// Mantissa and an exponent that is a multiple of three
+fn engineering(x: Num) -> (String, i32) {
+    let x = trim(x);
+    if x == 0. {
+        return ("0".to_owned(), 0);
+    }
+
+    let exp = exponent(x).div_euclid(3) * 3;
+    (trim(x / 10_f64.powi(exp)).to_string(), exp)
+}
+
+// Adds a comma between every three digits before the decimal point
+fn separate(number: &str) -> String {
+    if number.contains('e') {
+        return number.to_owned();
+    }
+
+    let (sign, digits) = match number.strip_prefix('-') {
+        Some(i) => ("-", i),
+        None => ("", number),
+    };
+    let split = digits
+        .find(|x: char| !x.is_ascii_digit())
+        .unwrap_or(digits.len());
+    let (whole, rest) = digits.split_at(split);
+
+    let mut out = sign.to_owned();
+    for (i, chr) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            out.push(',');
+        }
+        out.push(chr);
+    }
+    out + rest
+}
@@ -1,5 +1,17 @@
 use std::{fmt::Display, result};
 
+use complex::Complex;
+use dice::Dice;
+use number::Number;
+
+pub mod angle;
+pub mod complex;
+pub mod dice;
+pub mod dual;
+pub mod format;
+pub mod number;
+pub mod sequence;
+pub mod simplify;
 pub mod solver;
 pub mod tokens;
 pub mod tree;
@@ -7,22 +19,25 @@ pub mod tree;
 pub type Num = f64;
 pub type Result<T> = result::Result<T, Error>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     // == Basic tokens ==
     Number(Num),
     Op(Ops),
     Group(Vec<Token>),
+    List(Vec<Token>),
 
     // == Dynamic ==
     Func(String, Vec<Vec<Token>>),
     Var(String),
+    Dice(Dice),
 
     // == Misc ==
     Tree(Ops, Box<Token>, Box<Token>),
+    Complex(Complex),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Ops {
     Add,
     Sub,
@@ -30,37 +45,79 @@ pub enum Ops {
     Div,
     Pow,
     Mod,
+    Equal,
 }
 
 #[derive(Debug)]
 pub enum Error {
     // Tokenizer
     InvalidNumber(String),
+    InvalidDice(String),
 
     // Tree (more detailed?)
     InvalidExpression,
 
     // Solver
     UnknownIdentifier(String),
+    ExpectedNumber(String),
+    UnexpectedEquation,
+    RecursionLimit,
 
     // Function
     InvalidArgumentCount(String, usize, usize),
+    InvalidArgument(String, String),
+    NoConvergence(String),
+    NoSolution(String),
+    InfiniteSolutions(String),
+    OutOfRange(String, Num, Num, Num),
 }
 
 impl Ops {
     fn prio(&self) -> usize {
         match self {
+            Ops::Equal => 0,
             Ops::Add | Ops::Sub => 1,
             Ops::Mul | Ops::Div | Ops::Mod => 2,
             Ops::Pow => 3,
         }
     }
+
+    /// Applies the operation to two numbers, `Equal` has no numeric value.
+    pub fn apply<N: Number>(&self, left: N, right: N) -> Result<N> {
+        Ok(match self {
+            Ops::Add => left + right,
+            Ops::Sub => left - right,
+            Ops::Mul => left * right,
+            Ops::Div => left / right,
+            Ops::Pow => left.powf(right),
+            Ops::Mod => left % right,
+            Ops::Equal => return Err(Error::UnexpectedEquation),
+        })
+    }
+
+    // Operators are left associative, so only these can skip the parentheses on their right side
+    fn is_associative(&self) -> bool {
+        matches!(self, Ops::Add | Ops::Mul)
+    }
 }
 
 impl Token {
+    pub fn as_number(&self) -> Result<Num> {
+        match self {
+            Token::Number(n) => Ok(*n),
+            i => Err(Error::ExpectedNumber(i.to_string())),
+        }
+    }
+
     fn make_tree(self) -> Result<Token> {
         match self {
             Token::Group(tokens) => tree::create_tree(tokens),
+            Token::List(items) => Ok(Token::List(
+                items
+                    .into_iter()
+                    .map(|x| x.make_tree())
+                    .collect::<Result<_>>()?,
+            )),
             _ => Ok(self),
         }
     }
@@ -75,38 +132,71 @@ impl Display for Ops {
             Ops::Div => write!(f, "/"),
             Ops::Pow => write!(f, "^"),
             Ops::Mod => write!(f, "%"),
+            Ops::Equal => write!(f, "="),
         }
     }
 }
 
-impl Display for Token {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&match self {
-            Token::Number(n) => n.to_string(),
+impl Token {
+    /// Writes the token out like `Display`, but with every number written by `number`.
+    pub fn to_string_with(&self, number: &dyn Fn(Num) -> String) -> String {
+        let join = |tokens: &[Token], sep| {
+            tokens
+                .iter()
+                .map(|x| x.to_string_with(number))
+                .collect::<Vec<_>>()
+                .join(sep)
+        };
+
+        match self {
+            Token::Number(n) => number(*n),
             Token::Op(op) => op.to_string(),
-            Token::Group(tokens) => format!(
-                "({})",
-                tokens
-                    .iter()
-                    .map(|x| x.to_string())
-                    .collect::<Vec<_>>()
-                    .join(" ")
-            ),
+            Token::Group(tokens) => format!("({})", join(tokens, " ")),
+            Token::List(items) => format!("[{}]", join(items, ", ")),
             Token::Func(name, args) => format!(
                 "{}({})",
                 name,
                 args.iter()
-                    .map(|x| x
-                        .iter()
-                        .map(|x| x.to_string())
-                        .collect::<Vec<_>>()
-                        .join(" "))
+                    .map(|x| join(x, " "))
                     .collect::<Vec<_>>()
                     .join(", ")
             ),
             Token::Var(name) => name.to_string(),
-            Token::Tree(op, left, right) => format!("({} {} {})", left, op, right),
-        })
+            Token::Dice(dice) => dice.to_string(),
+            Token::Complex(n) => n.to_string_with(number),
+            Token::Tree(Ops::Mul, left, right) if **left == Token::Number(-1.) => {
+                format!("-{}", parenthesize(right, number, |_| true))
+            }
+            // Nested powers keep their parentheses, `a ^ b ^ c` is easy to misread
+            Token::Tree(op, left, right) => format!(
+                "{} {} {}",
+                parenthesize(left, number, |x| x < op.prio()
+                    || x == op.prio() && matches!(op, Ops::Pow)),
+                op,
+                parenthesize(right, number, |x| x < op.prio()
+                    || x == op.prio() && !op.is_associative())
+            ),
+        }
+    }
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_string_with(&|x| x.to_string()))
+    }
+}
+
+// Wraps trees whose operator priority matches `needs_parens` in parentheses
+fn parenthesize(
+    token: &Token,
+    number: &dyn Fn(Num) -> String,
+    needs_parens: impl Fn(usize) -> bool,
+) -> String {
+    match token {
+        Token::Tree(op, ..) if needs_parens(op.prio()) => {
+            format!("({})", token.to_string_with(number))
+        }
+        _ => token.to_string_with(number),
     }
 }
 
@@ -114,12 +204,28 @@ impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(&match self {
             Error::InvalidNumber(n) => format!("Invalid number: `{}`", n),
+            Error::InvalidDice(n) => format!("Invalid dice: `{}`", n),
             Error::InvalidExpression => "Invalid expression".to_string(),
             Error::UnknownIdentifier(n) => format!("Unknown identifier: `{}`", n),
+            Error::ExpectedNumber(n) => format!("Expected a number, got `{}`", n),
+            Error::UnexpectedEquation => {
+                "Equations can only be used as arguments to functions like `solve`".to_string()
+            }
+            Error::RecursionLimit => {
+                "Too much recursion, is a definition missing a starting value?".to_string()
+            }
             Error::InvalidArgumentCount(n, a, e) => format!(
                 "Invalid argument count for `{}`: expected {}, got {}",
                 n, e, a
             ),
+            Error::InvalidArgument(n, r) => format!("Invalid argument for `{}`: {}", n, r),
+            Error::NoConvergence(n) => format!("`{}` failed to converge", n),
+            Error::NoSolution(n) => format!("`{}` has no solution", n),
+            Error::InfiniteSolutions(n) => format!("`{}` has infinitely many solutions", n),
+            Error::OutOfRange(n, x, min, max) => format!(
+                "`{}` is outside the data for `{}`, which goes from {} to {}",
+                x, n, min, max
+            ),
         })
     }
 }
@@ -0,0 +1,312 @@
+//! Number types expressions can be evaluated with. Besides plain numbers, dual numbers carry
+//! a derivative along, which is how `grad` gets exact derivatives.
+
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
+
+use super::{dual::Dual, solver::Context, Num, Result, Token};
+use crate::funcs::Function;
+
+macro_rules! forward {
+    ($($func:ident),*) => {
+        $(
+            fn $func(self) -> Self {
+                Num::$func(self)
+            }
+        )*
+    };
+}
+
+pub trait Number:
+    Copy
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Rem<Output = Self>
+    + Neg<Output = Self>
+{
+    /// A number that does not change with anything.
+    fn constant(value: Num) -> Self;
+
+    /// The plain value, without anything else the number carries.
+    fn value(self) -> Num;
+
+    /// Calls a function with arguments of this type.
+    fn call(func: &dyn Function, args: Vec<Self>, context: &mut Context) -> Result<Self>;
+
+    fn powf(self, n: Self) -> Self;
+    fn floor(self) -> Self;
+    fn ceil(self) -> Self;
+    fn round(self) -> Self;
+    fn trunc(self) -> Self;
+    fn fract(self) -> Self;
+    fn abs(self) -> Self;
+    fn exp(self) -> Self;
+    fn exp2(self) -> Self;
+    fn ln(self) -> Self;
+    fn log2(self) -> Self;
+    fn log10(self) -> Self;
+    fn cbrt(self) -> Self;
+    fn exp_m1(self) -> Self;
+    fn ln_1p(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn asin(self) -> Self;
+    fn acos(self) -> Self;
+    fn atan(self) -> Self;
+    fn sinh(self) -> Self;
+    fn cosh(self) -> Self;
+    fn tanh(self) -> Self;
+    fn asinh(self) -> Self;
+    fn acosh(self) -> Self;
+    fn atanh(self) -> Self;
+    fn recip(self) -> Self;
+    fn to_degrees(self) -> Self;
+    fn to_radians(self) -> Self;
+    fn signum(self) -> Self;
+    fn atan2(self, other: Self) -> Self;
+
+    fn min(self, other: Self) -> Self {
+        match self.value() <= other.value() {
+            true => self,
+            false => other,
+        }
+    }
+
+    fn max(self, other: Self) -> Self {
+        match self.value() >= other.value() {
+            true => self,
+            false => other,
+        }
+    }
+
+    fn clamp(self, min: Self, max: Self) -> Self {
+        self.max(min).min(max)
+    }
+
+    fn log(self, base: Self) -> Self {
+        self.ln() / base.ln()
+    }
+
+    fn hypot(self, other: Self) -> Self {
+        (self * self + other * other).sqrt()
+    }
+}
+
+impl Number for Num {
+    fn constant(value: Num) -> Self {
+        value
+    }
+
+    fn value(self) -> Num {
+        self
+    }
+
+    fn call(func: &dyn Function, args: Vec<Self>, context: &mut Context) -> Result<Self> {
+        func.call(args.into_iter().map(Token::Number).collect(), context)
+    }
+
+    fn powf(self, n: Self) -> Self {
+        Num::powf(self, n)
+    }
+
+    fn atan2(self, other: Self) -> Self {
+        Num::atan2(self, other)
+    }
+
+    fn min(self, other: Self) -> Self {
+        Num::min(self, other)
+    }
+
+    fn max(self, other: Self) -> Self {
+        Num::max(self, other)
+    }
+
+    fn clamp(self, min: Self, max: Self) -> Self {
+        Num::clamp(self, min, max)
+    }
+
+    fn log(self, base: Self) -> Self {
+        Num::log(self, base)
+    }
+
+    fn hypot(self, other: Self) -> Self {
+        Num::hypot(self, other)
+    }
+
+    forward!(
+        floor, ceil, round, trunc, fract, abs, exp, exp2, ln, log2, log10, cbrt, exp_m1, ln_1p,
+        sqrt, sin, cos, tan, asin, acos, atan, sinh, cosh, tanh, asinh, acosh, atanh, recip,
+        to_degrees, to_radians, signum
+    );
+}
+
+impl Number for Dual {
+    fn constant(value: Num) -> Self {
+        Self::new(value, 0.)
+    }
+
+    fn value(self) -> Num {
+        self.re
+    }
+
+    fn call(func: &dyn Function, args: Vec<Self>, context: &mut Context) -> Result<Self> {
+        // Nothing to differentiate, so any function works
+        if args.iter().all(|x| x.eps == 0.) {
+            let args = args.into_iter().map(|x| x.re).collect();
+            return Num::call(func, args, context).map(Self::constant);
+        }
+
+        func.call_dual(args, context)
+    }
+
+    // d(x^y) = y x^(y - 1) dx + x^y ln(x) dy, skipping the parts that are zero so something
+    // like a negative base with a constant exponent does not give NaN from ln(x)
+    fn powf(self, n: Self) -> Self {
+        let value = self.re.powf(n.re);
+        let mut eps = 0.;
+        if self.eps != 0. {
+            eps += self.eps * n.re * self.re.powf(n.re - 1.);
+        }
+        if n.eps != 0. {
+            eps += n.eps * value * self.re.ln();
+        }
+        Self::new(value, eps)
+    }
+
+    fn floor(self) -> Self {
+        Self::constant(self.re.floor())
+    }
+
+    fn ceil(self) -> Self {
+        Self::constant(self.re.ceil())
+    }
+
+    fn round(self) -> Self {
+        Self::constant(self.re.round())
+    }
+
+    fn trunc(self) -> Self {
+        Self::constant(self.re.trunc())
+    }
+
+    fn fract(self) -> Self {
+        Self::new(self.re.fract(), self.eps)
+    }
+
+    fn abs(self) -> Self {
+        Self::new(self.re.abs(), self.eps * self.re.signum())
+    }
+
+    fn exp(self) -> Self {
+        self.chain(self.re.exp(), self.re.exp())
+    }
+
+    fn exp2(self) -> Self {
+        self.chain(self.re.exp2(), self.re.exp2() * std::f64::consts::LN_2)
+    }
+
+    fn ln(self) -> Self {
+        self.chain(self.re.ln(), self.re.recip())
+    }
+
+    fn log2(self) -> Self {
+        self.chain(self.re.log2(), (self.re * std::f64::consts::LN_2).recip())
+    }
+
+    fn log10(self) -> Self {
+        self.chain(self.re.log10(), (self.re * std::f64::consts::LN_10).recip())
+    }
+
+    fn cbrt(self) -> Self {
+        let value = self.re.cbrt();
+        self.chain(value, (3. * value * value).recip())
+    }
+
+    fn exp_m1(self) -> Self {
+        self.chain(self.re.exp_m1(), self.re.exp())
+    }
+
+    fn ln_1p(self) -> Self {
+        self.chain(self.re.ln_1p(), (1. + self.re).recip())
+    }
+
+    fn sqrt(self) -> Self {
+        let value = self.re.sqrt();
+        self.chain(value, (2. * value).recip())
+    }
+
+    fn sin(self) -> Self {
+        self.chain(self.re.sin(), self.re.cos())
+    }
+
+    fn cos(self) -> Self {
+        self.chain(self.re.cos(), -self.re.sin())
+    }
+
+    fn tan(self) -> Self {
+        let value = self.re.tan();
+        self.chain(value, 1. + value * value)
+    }
+
+    fn asin(self) -> Self {
+        self.chain(self.re.asin(), (1. - self.re * self.re).sqrt().recip())
+    }
+
+    fn acos(self) -> Self {
+        self.chain(self.re.acos(), -(1. - self.re * self.re).sqrt().recip())
+    }
+
+    fn atan(self) -> Self {
+        self.chain(self.re.atan(), (1. + self.re * self.re).recip())
+    }
+
+    fn sinh(self) -> Self {
+        self.chain(self.re.sinh(), self.re.cosh())
+    }
+
+    fn cosh(self) -> Self {
+        self.chain(self.re.cosh(), self.re.sinh())
+    }
+
+    fn tanh(self) -> Self {
+        let value = self.re.tanh();
+        self.chain(value, 1. - value * value)
+    }
+
+    fn asinh(self) -> Self {
+        self.chain(self.re.asinh(), (self.re * self.re + 1.).sqrt().recip())
+    }
+
+    fn acosh(self) -> Self {
+        self.chain(self.re.acosh(), (self.re * self.re - 1.).sqrt().recip())
+    }
+
+    fn atanh(self) -> Self {
+        self.chain(self.re.atanh(), (1. - self.re * self.re).recip())
+    }
+
+    fn recip(self) -> Self {
+        self.chain(self.re.recip(), -(self.re * self.re).recip())
+    }
+
+    fn to_degrees(self) -> Self {
+        self.chain(self.re.to_degrees(), 1_f64.to_degrees())
+    }
+
+    fn to_radians(self) -> Self {
+        self.chain(self.re.to_radians(), 1_f64.to_radians())
+    }
+
+    fn signum(self) -> Self {
+        Self::constant(self.re.signum())
+    }
+
+    fn atan2(self, other: Self) -> Self {
+        let (y, x) = (self, other);
+        let eps = (x.re * y.eps - y.re * x.eps) / (x.re * x.re + y.re * y.re);
+        Self::new(y.re.atan2(x.re), eps)
+    }
+}
@@ -0,0 +1,167 @@
+//! Sequences defined by recurrences, like `a(n) = a(n - 1) + a(n - 2), a(0) = 0, a(1) = 1`.
+
+use std::collections::BTreeMap;
+
+use super::{
+    solver::{func_args, Context},
+    Error, Num, Result, Token,
+};
+
+pub struct Sequence {
+    /// Index variable used in the rule
+    pub var: String,
+    pub rule: Option<Token>,
+    /// Values given directly, like `a(0) = 0`
+    pub values: BTreeMap<i64, Num>,
+    // Values worked out from the rule, cleared whenever a definition, constant or the angle mode
+    // changes
+    memo: BTreeMap<i64, Num>,
+}
+
+impl Sequence {
+    fn new() -> Self {
+        Self {
+            var: "n".to_owned(),
+            rule: None,
+            values: BTreeMap::new(),
+            memo: BTreeMap::new(),
+        }
+    }
+
+    fn get(&self, index: i64) -> Option<Num> {
+        self.values
+            .get(&index)
+            .or_else(|| self.memo.get(&index))
+            .copied()
+    }
+
+    // Closest index below `index` with a known value
+    fn known_below(&self, index: i64) -> Option<i64> {
+        let given = self.values.range(..index).next_back().map(|x| *x.0);
+        let memo = self.memo.range(..index).next_back().map(|x| *x.0);
+        given.max(memo)
+    }
+}
+
+/// Stores `left = right`, where `left` is either a rule like `a(n)` or a value like `a(0)`.
+pub fn define(left: &Token, right: &Token, context: &mut Context) -> Result<()> {
+    let Token::Func(name, args) = left else {
+        return Err(Error::InvalidExpression);
+    };
+    let name = name.to_lowercase();
+    if context.funcs.contains_key(&name) {
+        return Err(Error::InvalidArgument(
+            name,
+            "built-in functions can not be redefined".to_owned(),
+        ));
+    }
+
+    let args = func_args(args.to_owned());
+    if args.len() != 1 {
+        return Err(Error::InvalidArgumentCount(name, args.len(), 1));
+    }
+
+    // Values for indexes are worked out before anything changes
+    let value = match &args[0] {
+        Token::Var(_) => None,
+        index => {
+            let index = context.evaluate(index.to_owned())?;
+            if index.fract() != 0. {
+                return Err(Error::InvalidArgument(
+                    name,
+                    "values can only be given for whole indexes".to_owned(),
+                ));
+            }
+            Some((index as i64, context.evaluate(right.to_owned())?))
+        }
+    };
+
+    clear_memos(context);
+    let sequence = context.sequences.entry(name).or_insert_with(Sequence::new);
+    match (value, &args[0]) {
+        (Some((index, value)), _) => {
+            sequence.values.insert(index, value);
+        }
+        (None, Token::Var(var)) => {
+            sequence.var = var.to_lowercase();
+            sequence.rule = Some(right.to_owned());
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+/// Forgets every value worked out so far, for when something they could depend on changes.
+pub fn clear_memos(context: &mut Context) {
+    for i in context.sequences.values_mut() {
+        i.memo.clear();
+    }
+}
+
+/// Evaluates a call like `a(50)`. Values at whole indexes are remembered when the rule only
+/// depends on the index, and missing values are worked out upwards from the closest known one,
+/// so `a(n - 1)` is never more than one level deep.
+pub fn evaluate(name: &str, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+    let name = name.to_lowercase();
+    if args.len() != 1 {
+        return Err(Error::InvalidArgumentCount(name, args.len(), 1));
+    }
+
+    let n = context.evaluate(args[0].to_owned())?;
+    let index = (n.fract() == 0. && n.abs() < i64::MAX as Num).then_some(n as i64);
+    let sequence = &context.sequences[&name];
+    if let Some(value) = index.and_then(|x| sequence.get(x)) {
+        return Ok(value);
+    }
+
+    let Some(rule) = sequence.rule.to_owned() else {
+        return Err(Error::InvalidArgument(
+            name.to_owned(),
+            format!("no value or rule for `{}({})`", name, n),
+        ));
+    };
+    let var = sequence.var.to_owned();
+    let memoize = is_closed(&rule, &var, context);
+
+    let at = |x: Num, context: &mut Context| -> Result<Num> {
+        let value = context.evaluate_with(&rule, &var, x)?;
+        if memoize && x.fract() == 0. {
+            if let Some(i) = context.sequences.get_mut(&name) {
+                i.memo.insert(x as i64, value);
+            }
+        }
+        Ok(value)
+    };
+
+    if let (Some(index), true) = (index, memoize) {
+        if let Some(start) = context.sequences[&name].known_below(index) {
+            for i in start + 1..index {
+                at(i as Num, context)?;
+            }
+        }
+    }
+
+    at(n, context)
+}
+
+// Whether `token` only depends on `var`, so its value can be remembered
+fn is_closed(token: &Token, var: &str, context: &Context) -> bool {
+    match token {
+        Token::Number(_) | Token::Op(_) => true,
+        Token::Var(name) => name.to_lowercase() == var || context.is_constant(name),
+        Token::Tree(_, left, right) => {
+            is_closed(left, var, context) && is_closed(right, var, context)
+        }
+        Token::Func(name, args) => {
+            let name = name.to_lowercase();
+            let known = context.sequences.contains_key(&name)
+                || context.funcs.get(&name).is_some_and(|x| x.is_pure());
+            known && args.iter().flatten().all(|x| is_closed(x, var, context))
+        }
+        Token::List(items) | Token::Group(items) => {
+            items.iter().all(|x| is_closed(x, var, context))
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,362 @@
+//! Algebraic simplification of expression trees.
+
+use std::cmp::Ordering;
+
+use super::{
+    solver::{func_args, Context},
+    Num, Ops, Token,
+};
+
+/// Folds constant subtrees and calls to pure functions, removes identities like `x * 1`,
+/// collects like terms and powers of the same base and sorts terms into a consistent order.
+pub fn simplify(token: Token, context: &mut Context) -> Token {
+    match token {
+        Token::Tree(op, left, right) => {
+            let left = simplify(*left, context);
+            let right = simplify(*right, context);
+            simplify_tree(op, left, right, context)
+        }
+        Token::Func(name, args) => simplify_func(name, args, context),
+        Token::List(items) => {
+            Token::List(items.into_iter().map(|x| simplify(x, context)).collect())
+        }
+        i => i,
+    }
+}
+
+/// Builds a tree node out of two tokens.
+pub fn tree(op: Ops, left: Token, right: Token) -> Token {
+    Token::Tree(op, Box::new(left), Box::new(right))
+}
+
+/// Whether evaluating `token` always gives the same result without side effects.
+/// Dice and calls to functions that are not marked pure are not.
+pub fn is_pure(token: &Token, context: &Context) -> bool {
+    match token {
+        Token::Dice(_) => false,
+        Token::Func(name, args) => {
+            context
+                .funcs
+                .get(&name.to_lowercase())
+                .map(|x| x.is_pure())
+                .unwrap_or(false)
+                && args.iter().flatten().all(|x| is_pure(x, context))
+        }
+        Token::Tree(_, left, right) => is_pure(left, context) && is_pure(right, context),
+        Token::List(items) | Token::Group(items) => items.iter().all(|x| is_pure(x, context)),
+        _ => true,
+    }
+}
+
+/// Whether `token` is made of only numbers and built-in constants like `pi`.
+pub fn is_constant(token: &Token, context: &Context) -> bool {
+    match token {
+        Token::Number(_) => true,
+        Token::Var(name) => context.is_constant(name),
+        Token::Tree(_, left, right) => is_constant(left, context) && is_constant(right, context),
+        _ => false,
+    }
+}
+
+fn simplify_tree(op: Ops, left: Token, right: Token, context: &Context) -> Token {
+    if let (Token::Number(l), Token::Number(r)) = (&left, &right) {
+        if let Ok(value) = op.apply(*l, *r) {
+            return Token::Number(value);
+        }
+    }
+
+    // Rewriting could drop or merge dice rolls and side effects
+    if !is_pure(&left, context) || !is_pure(&right, context) {
+        return tree(op, left, right);
+    }
+
+    match op {
+        Ops::Add | Ops::Sub => {
+            let mut sum = Sum::default();
+            sum.add(left, 1.);
+            sum.add(right, if matches!(op, Ops::Sub) { -1. } else { 1. });
+            sum.build()
+        }
+        Ops::Mul | Ops::Div | Ops::Pow => {
+            let mut product = Product::default();
+            match op {
+                Ops::Mul => {
+                    product.add(left, Token::Number(1.), context);
+                    product.add(right, Token::Number(1.), context);
+                }
+                Ops::Div => {
+                    product.add(left, Token::Number(1.), context);
+                    product.add(right, Token::Number(-1.), context);
+                }
+                _ => product.add(left, right, context),
+            }
+            product.build()
+        }
+        _ => tree(op, left, right),
+    }
+}
+
+fn simplify_func(name: String, args: Vec<Vec<Token>>, context: &mut Context) -> Token {
+    let args = func_args(args)
+        .into_iter()
+        .map(|x| simplify(x, context))
+        .collect::<Vec<_>>();
+
+    // Constants like `pi` are only folded inside of function calls, so `pi / 2` stays readable
+    let func = context.funcs.get(&name.to_lowercase()).copied();
+    if let Some(func) = func.filter(|x| x.is_pure()) {
+        if args.iter().all(|x| is_constant(x, context)) {
+            if let Ok(value @ Token::Number(_)) = func.call_value(args.to_owned(), context) {
+                return value;
+            }
+        }
+    }
+
+    Token::Func(name, args.into_iter().map(|x| vec![x]).collect())
+}
+
+// Terms of a sum as (term without its coefficient, coefficient), constants use a term of 1
+#[derive(Default)]
+struct Sum {
+    terms: Vec<(Token, Num)>,
+}
+
+impl Sum {
+    fn add(&mut self, token: Token, sign: Num) {
+        match token {
+            Token::Tree(Ops::Add, left, right) => {
+                self.add(*left, sign);
+                self.add(*right, sign);
+            }
+            Token::Tree(Ops::Sub, left, right) => {
+                self.add(*left, sign);
+                self.add(*right, -sign);
+            }
+            Token::Number(n) => self.push(Token::Number(1.), n * sign),
+            token => {
+                let (coeff, term) = Product::of(token).split_coeff();
+                self.push(term, coeff * sign)
+            }
+        }
+    }
+
+    fn push(&mut self, term: Token, coeff: Num) {
+        match self.terms.iter_mut().find(|x| x.0 == term) {
+            Some(i) => i.1 += coeff,
+            None => self.terms.push((term, coeff)),
+        }
+    }
+
+    // Highest degree first, with the constant at the end
+    fn build(mut self) -> Token {
+        self.terms.sort_by(|a, b| {
+            let is_const = |x: &Token| matches!(x, Token::Number(_));
+            is_const(&a.0)
+                .cmp(&is_const(&b.0))
+                .then(degree(&b.0).total_cmp(&degree(&a.0)))
+                .then(a.0.to_string().cmp(&b.0.to_string()))
+        });
+
+        sum_of(self.terms)
+    }
+}
+
+// Factors of a product as (base, exponent)
+struct Product {
+    coeff: Num,
+    factors: Vec<(Token, Token)>,
+}
+
+impl Default for Product {
+    fn default() -> Self {
+        Self {
+            coeff: 1.,
+            factors: Vec::new(),
+        }
+    }
+}
+
+impl Product {
+    // Products built by `build` only contain simplified numbers and factors
+    fn of(token: Token) -> Self {
+        let mut out = Self::default();
+        out.add_simplified(token, 1.);
+        out
+    }
+
+    fn add(&mut self, token: Token, exponent: Token, context: &Context) {
+        // (a * b)^n = a^n * b^n and (a^b)^n = a^(b * n) only hold for integer n
+        let integer = match exponent {
+            Token::Number(n) if n.fract() == 0. => Some(n),
+            _ => None,
+        };
+
+        match (token, integer) {
+            (Token::Number(n), Some(_)) | (Token::Number(n @ 1.), None) => {
+                self.coeff *= n.powf(integer.unwrap_or(1.))
+            }
+            (Token::Tree(Ops::Mul, left, right), Some(_)) => {
+                self.add(*left, exponent.to_owned(), context);
+                self.add(*right, exponent, context);
+            }
+            (Token::Tree(Ops::Div, left, right), Some(n)) => {
+                self.add(*left, exponent, context);
+                self.add(*right, Token::Number(-n), context);
+            }
+            (Token::Tree(Ops::Pow, base, power), Some(_)) => {
+                let power = simplify_tree(Ops::Mul, *power, exponent, context);
+                self.add(*base, power, context);
+            }
+            (token, _) => match self.factors.iter_mut().find(|x| x.0 == token) {
+                Some(i) => i.1 = simplify_tree(Ops::Add, i.1.to_owned(), exponent, context),
+                None => self.factors.push((token, exponent)),
+            },
+        }
+    }
+
+    // Like `add` for tokens that are already simplified, so nothing needs merging
+    fn add_simplified(&mut self, token: Token, exponent: Num) {
+        match token {
+            Token::Number(n) => self.coeff *= n.powf(exponent),
+            Token::Tree(Ops::Mul, left, right) => {
+                self.add_simplified(*left, exponent);
+                self.add_simplified(*right, exponent);
+            }
+            Token::Tree(Ops::Div, left, right) => {
+                self.add_simplified(*left, exponent);
+                self.add_simplified(*right, -exponent);
+            }
+            Token::Tree(Ops::Pow, base, power) if !matches!(*base, Token::Number(_)) => {
+                match *power {
+                    Token::Number(n) => self.factors.push((*base, Token::Number(n * exponent))),
+                    power if exponent == 1. => self.factors.push((*base, power)),
+                    power => self
+                        .factors
+                        .push((*base, tree(Ops::Mul, power, Token::Number(exponent)))),
+                }
+            }
+            token => self.factors.push((token, Token::Number(exponent))),
+        }
+    }
+
+    fn split_coeff(self) -> (Num, Token) {
+        let coeff = self.coeff;
+        let term = Self {
+            coeff: 1.,
+            factors: self.factors,
+        };
+        (coeff, term.build_sorted())
+    }
+
+    fn build(mut self) -> Token {
+        self.factors.retain(|x| x.1 != Token::Number(0.));
+        if self.coeff == 0. {
+            return Token::Number(0.);
+        }
+
+        self.factors.sort_by(|a, b| factor_order(&a.0, &b.0));
+        self.build_sorted()
+    }
+
+    // Factors with negative exponents go in the denominator, as do coefficients like 1/3
+    fn build_sorted(self) -> Token {
+        let (mut num, mut den) = (Vec::new(), Vec::new());
+        match self.coeff {
+            c if c.fract() != 0. && is_integer(c.recip()) => {
+                den.push(Token::Number(c.recip().abs().round()));
+                num.push(Token::Number(c.signum()));
+            }
+            c => num.push(Token::Number(c)),
+        }
+
+        for (base, exponent) in self.factors {
+            match exponent {
+                Token::Number(n) if n < 0. => den.push(power(base, -n)),
+                Token::Number(n) => num.push(power(base, n)),
+                exponent => num.push(tree(Ops::Pow, base, exponent)),
+            }
+        }
+
+        // Only keep a coefficient of 1 when there is nothing else
+        if num.len() > 1 && num[0] == Token::Number(1.) {
+            num.remove(0);
+        }
+
+        let num = multiply(num);
+        match den.is_empty() {
+            true => num,
+            false => tree(Ops::Div, num, multiply(den)),
+        }
+    }
+}
+
+// Allows for the rounding error of something like 1 / 12
+fn is_integer(x: Num) -> bool {
+    (x - x.round()).abs() <= 1e-12 * x.abs()
+}
+
+fn power(base: Token, exponent: Num) -> Token {
+    match exponent {
+        1. => base,
+        n => tree(Ops::Pow, base, Token::Number(n)),
+    }
+}
+
+fn multiply(factors: Vec<Token>) -> Token {
+    factors
+        .into_iter()
+        .reduce(|a, b| tree(Ops::Mul, a, b))
+        .unwrap_or(Token::Number(1.))
+}
+
+/// Adds up terms with their coefficients in order, skipping zeros and subtracting negative terms.
+pub fn sum_of(terms: impl IntoIterator<Item = (Token, Num)>) -> Token {
+    let mut out: Option<Token> = None;
+    for (term, coeff) in terms.into_iter().filter(|x| x.1 != 0.) {
+        out = Some(match out {
+            None => with_coeff(term, coeff),
+            Some(i) if coeff < 0. => tree(Ops::Sub, i, with_coeff(term, -coeff)),
+            Some(i) => tree(Ops::Add, i, with_coeff(term, coeff)),
+        });
+    }
+
+    out.unwrap_or(Token::Number(0.))
+}
+
+/// Multiplies a term by a number, giving forms like `2 * x` or `x ^ 3 / 6`.
+pub fn with_coeff(term: Token, coeff: Num) -> Token {
+    match term {
+        Token::Number(n) => Token::Number(n * coeff),
+        term => {
+            let mut product = Product::of(term);
+            product.coeff *= coeff;
+            product.build_sorted()
+        }
+    }
+}
+
+// Variables come first, then function calls, then everything else
+fn factor_order(a: &Token, b: &Token) -> Ordering {
+    let rank = |x: &Token| match x {
+        Token::Var(_) => 0,
+        Token::Func(..) => 1,
+        _ => 2,
+    };
+
+    rank(a)
+        .cmp(&rank(b))
+        .then(a.to_string().cmp(&b.to_string()))
+}
+
+// Total power of the variables in a term, used to sort polynomials
+fn degree(term: &Token) -> Num {
+    Product::of(term.to_owned())
+        .factors
+        .iter()
+        .filter(|x| matches!(x.0, Token::Var(_)))
+        .map(|x| match x.1 {
+            Token::Number(n) => n,
+            _ => 0.,
+        })
+        .sum()
+}
@@ -1,19 +1,52 @@
 use std::{collections::HashMap, f64};
 
+use colored::Colorize;
 use convert_case::{Case, Casing};
+use rand::{rngs::StdRng, SeedableRng};
 
-use super::{tree::create_tree, Error, Num, Ops, Result, Token};
-use crate::funcs::{Function, FUNCTIONS};
+use super::{
+    angle::AngleMode,
+    format::Format,
+    number::Number,
+    sequence::{self, Sequence},
+    simplify::simplify,
+    tree::create_tree,
+    Error, Num, Ops, Result, Token,
+};
+use crate::funcs::{depends_on, Function, FUNCTIONS};
 
 const CONSTANTS: &[(&str, Token)] = &[
     ("pi", Token::Number(f64::consts::PI)),
     ("e", Token::Number(f64::consts::E)),
     ("tau", Token::Number(f64::consts::TAU)),
+    ("inf", Token::Number(f64::INFINITY)),
 ];
 
+// Nested evaluations allowed before giving up, well before the stack runs out
+const MAX_DEPTH: usize = 1000;
+
 pub struct Context {
     pub vars: HashMap<String, Token>,
     pub funcs: HashMap<String, &'static dyn Function>,
+    /// Sequences defined like `a(n) = a(n - 1) + 1, a(0) = 0`
+    pub sequences: HashMap<String, Sequence>,
+    pub rng: StdRng,
+
+    /// Variables bound by functions like `integrate`, these shadow `vars`
+    locals: Vec<(String, Token)>,
+    depth: usize,
+
+    /// Print the individual dice of every roll
+    pub show_rolls: bool,
+
+    /// Simplify expressions before evaluating them
+    pub simplify: bool,
+
+    /// How results are written out
+    pub format: Format,
+
+    /// Unit of the angles given to and returned by trigonometric functions
+    angle: AngleMode,
 }
 
 impl Context {
@@ -25,51 +58,244 @@ impl Context {
                 .collect(),
             funcs: FUNCTIONS
                 .iter()
-                .map(|x| (x.name().to_case(Case::Snake), *x))
+                .map(|x| (func_name(x.name()), *x))
                 .collect(),
+            sequences: HashMap::new(),
+            rng: StdRng::from_entropy(),
+            locals: Vec::new(),
+            depth: 0,
+            show_rolls: false,
+            simplify: false,
+            format: Format::default(),
+            angle: AngleMode::default(),
         }
     }
 
     pub fn set_var(&mut self, name: &str, value: Token) {
         self.vars.insert(name.to_string(), value);
+        // Remembered values only use the index and constants, so other variables like `ans`
+        // can change without losing them
+        if CONSTANTS.iter().any(|(n, _)| *n == name.to_lowercase()) {
+            sequence::clear_memos(self);
+        }
+    }
+
+    pub fn angle(&self) -> AngleMode {
+        self.angle
+    }
+
+    /// Changes the angle mode, forgetting sequence values that could have used the old one.
+    pub fn set_angle(&mut self, angle: AngleMode) {
+        self.angle = angle;
+        sequence::clear_memos(self);
+    }
+
+    /// Runs `f` with `name` bound to `value`, the binding is removed afterwards.
+    pub fn with_local<T>(&mut self, name: &str, value: Num, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.with_locals([(name, value)], f)
+    }
+
+    /// Like `with_local`, but binds several variables at once.
+    pub fn with_locals<'a, T>(
+        &mut self,
+        bindings: impl IntoIterator<Item = (&'a str, Num)>,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        let len = self.locals.len();
+        self.locals.extend(
+            bindings
+                .into_iter()
+                .map(|(name, value)| (name.to_lowercase(), Token::Number(value))),
+        );
+        let out = f(self);
+        self.locals.truncate(len);
+        out
+    }
+
+    /// Evaluates `tree` with `name` bound to `value`.
+    pub fn evaluate_with(&mut self, tree: &Token, name: &str, value: Num) -> Result<Num> {
+        self.with_local(name, value, |ctx| ctx.evaluate(tree.to_owned()))
+    }
+
+    /// Optional pass between `create_tree` and `evaluate`, folds constants when enabled.
+    pub fn optimize(&mut self, tree: Token) -> Token {
+        match self.simplify {
+            true => simplify(tree, self),
+            false => tree,
+        }
+    }
+
+    /// Whether `name` still holds one of the built-in constants like `pi`.
+    pub fn is_constant(&self, name: &str) -> bool {
+        CONSTANTS
+            .iter()
+            .any(|(n, v)| *n == name.to_lowercase() && self.get_var(name) == Some(v))
+    }
+
+    pub fn get_var(&self, name: &str) -> Option<&Token> {
+        let name = name.to_lowercase();
+        self.locals
+            .iter()
+            .rev()
+            .find(|x| x.0 == name)
+            .map(|x| &x.1)
+            .or_else(|| self.vars.get(&name))
     }
 
     pub fn evaluate(&mut self, tree: Token) -> Result<Num> {
+        self.evaluate_as(tree, &[])
+    }
+
+    /// Evaluates `tree` with any kind of number, like dual numbers for exact derivatives.
+    /// The variables in `bindings` shadow all others, anything that does not depend on them
+    /// is evaluated as usual and becomes a constant.
+    pub fn evaluate_as<N: Number>(&mut self, tree: Token, bindings: &[(&str, N)]) -> Result<N> {
+        // Definitions like `a(n) = a(n + 1)` would otherwise recurse until the stack overflows
+        if self.depth >= MAX_DEPTH {
+            return Err(Error::RecursionLimit);
+        }
+
+        self.depth += 1;
+        let out = self.evaluate_token(tree, bindings);
+        self.depth -= 1;
+        out
+    }
+
+    fn evaluate_token<N: Number>(&mut self, tree: Token, bindings: &[(&str, N)]) -> Result<N> {
+        if !bindings.is_empty() && !bindings.iter().any(|x| depends_on(&tree, x.0, self)) {
+            return self.evaluate(tree).map(N::constant);
+        }
+
         match tree {
             Token::Tree(op, left, right) => {
-                let left = self.evaluate(*left)?;
-                let right = self.evaluate(*right)?;
-
-                Ok(match op {
-                    Ops::Add => left + right,
-                    Ops::Sub => left - right,
-                    Ops::Mul => left * right,
-                    Ops::Div => left / right,
-                    Ops::Pow => left.powf(right),
-                    Ops::Mod => left % right,
-                })
+                let left = self.evaluate_as(*left, bindings)?;
+                let right = self.evaluate_as(*right, bindings)?;
+                op.apply(left, right)
+            }
+            Token::Number(n) => Ok(N::constant(n)),
+            Token::Var(n) => match bindings.iter().find(|x| x.0 == n.to_lowercase()) {
+                Some(i) => Ok(i.1),
+                None => {
+                    let value = self
+                        .get_var(&n)
+                        .cloned()
+                        .ok_or(Error::UnknownIdentifier(n))?;
+                    self.evaluate_as(value, bindings)
+                }
+            },
+            Token::Dice(dice) => {
+                let rolls = dice.roll(&mut self.rng)?;
+                let total = dice.total(&rolls);
+
+                if self.show_rolls {
+                    let kept = dice.kept(&rolls);
+                    let rolls = rolls
+                        .iter()
+                        .enumerate()
+                        .map(|(i, x)| match kept.contains(&i) {
+                            true => x.to_string().normal(),
+                            false => x.to_string().dimmed().strikethrough(),
+                        })
+                        .map(|x| x.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    println!(" [ROLL] {} = [{}] = {}", dice, rolls, total);
+                }
+
+                Ok(N::constant(total as Num))
+            }
+            // Sequences only have values at whole indexes, so like `floor` they are constant
+            // wherever they can be differentiated
+            Token::Func(n, args) if self.sequences.contains_key(&n.to_lowercase()) => {
+                let args = func_args(args)
+                    .into_iter()
+                    .map(|x| Ok(Token::Number(self.evaluate_as(x, bindings)?.value())))
+                    .collect::<Result<Vec<_>>>()?;
+                sequence::evaluate(&n, args, self).map(N::constant)
             }
-            Token::Number(n) => Ok(n),
-            Token::Var(n) => self.evaluate(
-                self.vars
-                    .get(&n.to_lowercase())
-                    .cloned()
-                    .ok_or(Error::UnknownIdentifier(n))?,
-            ),
             Token::Func(n, args) => {
-                let func = self
+                let func = *self
                     .funcs
                     .get(&n.to_lowercase())
                     .ok_or(Error::UnknownIdentifier(n))?;
+                let args = func_args(args);
+
+                // Without anything bound, functions get their arguments as they are, which
+                // lets ones like `integrate` work on expressions
+                if bindings.is_empty() {
+                    return func.call(args, self).map(N::constant);
+                }
 
-                Ok(func.call(
-                    args.iter()
-                        .flat_map(|x| create_tree(x.to_owned()))
-                        .collect(),
-                    self,
-                )?)
+                // Symbolic functions give an expression, which can be evaluated instead
+                if func.is_symbolic() {
+                    let expr = func.call_value(args, self)?;
+                    return self.evaluate_as(expr, bindings);
+                }
+
+                let args = args
+                    .into_iter()
+                    .map(|x| self.evaluate_as(x, bindings))
+                    .collect::<Result<Vec<_>>>()?;
+                N::call(func, args, self)
             }
+            Token::Complex(n) if n.im == 0. => Ok(N::constant(n.re)),
+            Token::List(_) | Token::Complex(_) => Err(Error::ExpectedNumber(tree.to_string())),
             _ => panic!("Invalid token {:?}", tree),
         }
     }
+
+    /// Stores definitions like `a(n) = a(n - 1) + 1` or `a(0) = 1` and evaluates anything else.
+    /// Definitions are given back as they are.
+    pub fn run(&mut self, tree: Token) -> Result<Token> {
+        match tree {
+            Token::Tree(Ops::Equal, left, right) if matches!(*left, Token::Func(..)) => {
+                sequence::define(&left, &right, self)?;
+                Ok(Token::Tree(Ops::Equal, left, right))
+            }
+            tree => self.evaluate_value(tree),
+        }
+    }
+
+    /// Like `evaluate`, but allows results that are not numbers, like lists or expressions.
+    pub fn evaluate_value(&mut self, tree: Token) -> Result<Token> {
+        match tree {
+            Token::Func(ref n, _) if self.sequences.contains_key(&n.to_lowercase()) => {
+                self.evaluate(tree).map(Token::Number)
+            }
+            Token::Func(n, args) => {
+                let func = *self
+                    .funcs
+                    .get(&n.to_lowercase())
+                    .ok_or(Error::UnknownIdentifier(n))?;
+                func.call_value(func_args(args), self)
+            }
+            Token::List(items) => Ok(Token::List(
+                items
+                    .into_iter()
+                    .map(|x| self.evaluate_value(x))
+                    .collect::<Result<_>>()?,
+            )),
+            Token::Var(ref n) => match self.get_var(n) {
+                Some(i @ (Token::List(_) | Token::Tree(..) | Token::Complex(_))) => {
+                    Ok(i.to_owned())
+                }
+                _ => self.evaluate(tree).map(Token::Number),
+            },
+            _ => self.evaluate(tree).map(Token::Number),
+        }
+    }
+}
+
+// Functions named after their type, like `Log10`, are called as `log_10`. Names that are
+// already lowercase, like `bessel_j0`, are kept as they are.
+fn func_name(name: &str) -> String {
+    match name.chars().any(|x| x.is_uppercase()) {
+        true => name.to_case(Case::Snake),
+        false => name.to_owned(),
+    }
+}
+
+/// Builds the trees of the arguments of a function token.
+pub fn func_args(args: Vec<Vec<Token>>) -> Vec<Token> {
+    args.into_iter().flat_map(create_tree).collect()
 }
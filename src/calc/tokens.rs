@@ -1,31 +1,59 @@
-use super::{Error, Num, Ops, Result, Token};
+use std::ops::Range;
+
+use super::{dice::Dice, Error, Num, Ops, Result, Token};
 
 struct TokenizeContext {
     out: Vec<Token>,
     working: String,
 
     next_neg: bool,
+    // Indexes of non number tokens in `out` that had a unary minus
+    negate: Vec<usize>,
+    // Indexes of numbers that took the sign of a unary minus, like `-2`
+    negative_numbers: Vec<usize>,
     group_depth: usize,
+    group_kind: char,
     is_num: bool,
 }
 
 pub fn tokenize(inp: &str) -> Result<Vec<Token>> {
     let mut ctx = TokenizeContext::new();
 
-    for i in inp.chars() {
-        match i {
-            '(' if ctx.group_depth > 0 => ctx.group_depth += 1,
-            ')' if ctx.group_depth > 0 => ctx.group_depth -= 1,
-            _ => {}
-        };
-
+    let mut chars = inp.chars().peekable();
+    while let Some(i) = chars.next() {
         match i {
             i if i.is_whitespace() => continue,
 
             // Groups
-            '(' if ctx.group_depth == 0 => {
+            '(' | '[' if ctx.group_depth > 0 => {
+                ctx.group_depth += 1;
+                ctx.working.push(i);
+            }
+            ')' | ']' if ctx.group_depth > 1 => {
+                ctx.group_depth -= 1;
+                ctx.working.push(i);
+            }
+            '(' | '[' => {
                 flush_working(&mut ctx)?;
+                if ctx.next_neg {
+                    ctx.negate.push(ctx.out.len());
+                    ctx.next_neg = false;
+                }
                 ctx.group_depth += 1;
+                ctx.group_kind = i;
+            }
+            ')' | ']' if ctx.group_depth == 0 || !closes(ctx.group_kind, i) => {
+                return Err(Error::InvalidExpression)
+            }
+            ']' => {
+                ctx.group_depth = 0;
+                ctx.out.push(Token::List(
+                    tokenize_args(&ctx.working)?
+                        .into_iter()
+                        .map(Token::Group)
+                        .collect(),
+                ));
+                ctx.working.clear();
             }
             ')' if matches!(ctx.out.last(), Some(Token::Var(_))) => {
                 ctx.group_depth = 0;
@@ -35,12 +63,21 @@ pub fn tokenize(inp: &str) -> Result<Vec<Token>> {
                 );
                 ctx.working.clear();
             }
-            ')' if ctx.group_depth == 0 => {
+            ')' => {
+                ctx.group_depth = 0;
                 ctx.out.push(Token::Group(tokenize(&ctx.working)?));
                 ctx.working.clear();
             }
             i if ctx.group_depth > 0 => ctx.working.push(i),
 
+            // The sign of an exponent, like `1e-5`
+            '-' | '+'
+                if is_exponent_prefix(&ctx.working)
+                    && chars.peek().is_some_and(char::is_ascii_digit) =>
+            {
+                ctx.working.push(i)
+            }
+
             // Operations
             '-' => {
                 flush_working(&mut ctx)?;
@@ -54,7 +91,18 @@ pub fn tokenize(inp: &str) -> Result<Vec<Token>> {
             '*' => add_op(Ops::Mul, &mut ctx)?,
             '/' => add_op(Ops::Div, &mut ctx)?,
             '^' => add_op(Ops::Pow, &mut ctx)?,
+            '%' if is_dice_prefix(&ctx.working) => ctx.working.push(i),
             '%' => add_op(Ops::Mod, &mut ctx)?,
+            '=' => add_op(Ops::Equal, &mut ctx)?,
+
+            // Degrees, like `30°`, apply to the value just before
+            '°' => {
+                flush_working(&mut ctx)?;
+                match ctx.out.pop() {
+                    Some(Token::Op(_)) | None => return Err(Error::InvalidExpression),
+                    Some(i) => ctx.out.push(Token::Func("deg".to_owned(), vec![vec![i]])),
+                }
+            }
 
             // Numbers
             _ => {
@@ -64,16 +112,219 @@ pub fn tokenize(inp: &str) -> Result<Vec<Token>> {
         }
     }
 
+    if ctx.group_depth > 0 {
+        return Err(Error::InvalidExpression);
+    }
+
     if !ctx.working.is_empty() {
         add_num(&mut ctx)?;
     }
 
+    // Powers bind tighter than a unary minus, so `-2^2` is `-(2^2)`. Negative numbers that are
+    // raised to a power are turned back into a negation of the whole power.
+    for i in ctx.negative_numbers {
+        if let (Token::Number(x), Some(Token::Op(Ops::Pow))) = (&ctx.out[i], ctx.out.get(i + 1)) {
+            ctx.out[i] = Token::Number(-x);
+            ctx.negate.push(i);
+        }
+    }
+
+    // Going from the end keeps the indexes of earlier negations in place
+    ctx.negate.sort_unstable();
+    for i in ctx.negate.into_iter().rev() {
+        let mut end = i + 1;
+        while matches!(ctx.out.get(end), Some(Token::Op(Ops::Pow))) && end + 1 < ctx.out.len() {
+            end += 2;
+        }
+
+        let mut group = vec![Token::Number(-1.), Token::Op(Ops::Mul)];
+        group.extend(ctx.out.drain(i..end));
+        ctx.out.insert(i, Token::Group(group));
+    }
+
     Ok(ctx.out)
 }
 
+/// Splits a line into its comma separated statements and tokenizes each of them.
+pub fn tokenize_statements(inp: &str) -> Result<Vec<Vec<Token>>> {
+    tokenize_args(inp)
+}
+
+/// What a piece of the input is, as far as can be told without evaluating it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanKind {
+    Number,
+    Dice,
+    /// A name, `call` when it is followed by an opening parenthesis. `bound` when a call around
+    /// it takes it as a variable, like `k` in `sum(k^2, k, 1, 3)` or `n` in `a(n) = a(n - 1)`.
+    Ident {
+        call: bool,
+        bound: bool,
+    },
+    Operator,
+    Comma,
+    /// `matched` when it has a partner of the same kind
+    Bracket {
+        matched: bool,
+    },
+    /// Something that would not tokenize, like `1.2.3`
+    Invalid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub kind: SpanKind,
+    /// Byte range in the input
+    pub range: Range<usize>,
+}
+
+/// Splits input into classified spans, like `tokenize` but without failing on anything, so that
+/// incomplete input can be highlighted while it is typed. Whitespace is left out. `takes_names`
+/// tells which functions take plain names as arguments, see `Function::takes_names`.
+pub fn classify(inp: &str, takes_names: impl Fn(&str) -> bool) -> Vec<Span> {
+    let mut out = Vec::new();
+    // Spans and kinds of the brackets still waiting to be closed
+    let mut open = Vec::new();
+    let mut word_start = None;
+
+    for (i, chr) in inp.char_indices() {
+        let in_word = match chr {
+            '%' => word_start.is_some_and(|x| is_dice_prefix(&inp[x..i])),
+            '-' | '+' => word_start.is_some_and(|x| {
+                is_exponent_prefix(&inp[x..i])
+                    && inp[i + 1..].starts_with(|x: char| x.is_ascii_digit())
+            }),
+            _ => !(chr.is_whitespace() || "()[]+-*/^%=°,".contains(chr)),
+        };
+        if in_word {
+            word_start.get_or_insert(i);
+            continue;
+        }
+
+        if let Some(start) = word_start.take() {
+            add_word(inp, start..i, &mut out);
+        }
+        let range = i..i + chr.len_utf8();
+        match chr {
+            i if i.is_whitespace() => {}
+            '(' | '[' => {
+                open.push((out.len(), chr));
+                out.push(Span {
+                    kind: SpanKind::Bracket { matched: false },
+                    range,
+                });
+            }
+            ')' | ']' => {
+                let matched = matches!(open.last(), Some((_, kind)) if closes(*kind, chr));
+                if matched {
+                    let (index, _) = open.pop().unwrap();
+                    out[index].kind = SpanKind::Bracket { matched: true };
+                }
+                out.push(Span {
+                    kind: SpanKind::Bracket { matched },
+                    range,
+                });
+            }
+            ',' => out.push(Span {
+                kind: SpanKind::Comma,
+                range,
+            }),
+            _ => out.push(Span {
+                kind: SpanKind::Operator,
+                range,
+            }),
+        }
+    }
+
+    if let Some(start) = word_start {
+        add_word(inp, start..inp.len(), &mut out);
+    }
+
+    bind_names(inp, &mut out, takes_names);
+    out
+}
+
 // TOKENIZE [Number(1.0), Op(Pow), Number(2.0), Op(Sub), Number(4.0), Op(Mul), Number(1.0), Op(Mul), Number(0.25)]
 // TOKENIZE [Number(1.0), Op(Pow), Number(2.0), Op(Sub), Number(4.0), Op(Mul), Number(1.0), Op(Mul), Number(0.25)]
 
+// Marks the names a call binds. Calls like `sum(expr, var, ...)` or `linsolve(eqs, [vars])` take
+// their variables as plain names after the first argument and bind them for the whole call. A
+// definition like `a(n) = ...` binds `n` up to the end of the statement.
+fn bind_names(inp: &str, spans: &mut [Span], takes_names: impl Fn(&str) -> bool) {
+    let text = |span: &Span| &inp[span.range.clone()];
+    let is_name = |span: &Span| matches!(span.kind, SpanKind::Ident { call: false, .. });
+
+    for i in 0..spans.len() {
+        if !matches!(spans[i].kind, SpanKind::Ident { call: true, .. })
+            || spans.get(i + 1).map(text) != Some("(")
+        {
+            continue;
+        }
+
+        let mut args = Vec::new();
+        let mut start = i + 2;
+        let close = loop {
+            let end = group_end(inp, spans, start, true);
+            args.push(&spans[start..end]);
+            match spans.get(end) {
+                Some(span) if span.kind == SpanKind::Comma => start = end + 1,
+                _ => break end,
+            }
+        };
+
+        let mut names = Vec::new();
+        let mut scope = i + 2..close;
+        match args.as_slice() {
+            // A definition like `a(n) = ...`
+            [[arg]] if is_name(arg) && spans.get(close + 1).map(text) == Some("=") => {
+                names.push(text(arg).to_lowercase());
+                scope = i + 2..group_end(inp, spans, close + 1, true);
+            }
+            args if takes_names(&text(&spans[i]).to_lowercase()) => {
+                for arg in args.iter().skip(1) {
+                    match arg {
+                        [name] if is_name(name) => names.push(text(name).to_lowercase()),
+                        [open, inner @ .., close] if text(open) == "[" && text(close) == "]" => {
+                            names.extend(
+                                inner
+                                    .iter()
+                                    .filter(|x| is_name(x))
+                                    .map(|x| text(x).to_lowercase()),
+                            )
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => continue,
+        }
+
+        for span in &mut spans[scope] {
+            if let SpanKind::Ident { call, .. } = span.kind {
+                if names.contains(&inp[span.range.clone()].to_lowercase()) {
+                    span.kind = SpanKind::Ident { call, bound: true };
+                }
+            }
+        }
+    }
+}
+
+// Index of the first closing bracket from `start` that is not inside a nested group, or of the
+// first such comma when `commas` is set
+fn group_end(inp: &str, spans: &[Span], start: usize, commas: bool) -> usize {
+    let mut depth = 0;
+    for (i, span) in spans.iter().enumerate().skip(start) {
+        match (&inp[span.range.clone()], span.kind) {
+            ("(" | "[", _) => depth += 1,
+            (")" | "]", _) if depth == 0 => return i,
+            (")" | "]", _) => depth -= 1,
+            (_, SpanKind::Comma) if commas && depth == 0 => return i,
+            _ => {}
+        }
+    }
+    spans.len()
+}
+
 impl TokenizeContext {
     fn new() -> Self {
         Self {
@@ -81,7 +332,10 @@ impl TokenizeContext {
             working: String::new(),
 
             next_neg: false,
+            negate: Vec::new(),
+            negative_numbers: Vec::new(),
             group_depth: 0,
+            group_kind: '(',
             is_num: true,
         }
     }
@@ -91,20 +345,69 @@ fn is_digit(chr: char) -> bool {
     matches!(chr, '0'..='9' | '.')
 }
 
+// `d` or `3d`, waiting on a `%` to become `d%` / `3d%`
+fn is_dice_prefix(working: &str) -> bool {
+    working
+        .strip_suffix('d')
+        .map(|x| x.chars().all(|x| x.is_ascii_digit()))
+        .unwrap_or(false)
+}
+
+// A number waiting on the digits of its exponent, like `1e` in `1e-5`
+fn is_exponent_prefix(working: &str) -> bool {
+    working
+        .strip_suffix(['e', 'E'])
+        .is_some_and(|x| x.starts_with(|x: char| x.is_ascii_digit()) && x.chars().all(is_digit))
+}
+
+// Numbers written with an exponent, like `2.5e3`
+fn is_scientific(word: &str) -> bool {
+    word.starts_with(|x: char| x.is_ascii_digit()) && word.parse::<Num>().is_ok()
+}
+
 fn add_num(ctx: &mut TokenizeContext) -> Result<()> {
-    if !ctx.is_num {
-        ctx.out.push(Token::Var(ctx.working.to_string()));
+    let working = ctx.working.to_owned();
+    if ctx.is_num || is_scientific(&working) {
+        return add_number(ctx, &working);
+    }
+
+    let dice = Dice::parse(&working);
+    // A number just before a name multiplies it, like `2x`
+    if let (None, Some(end)) = (&dice, coefficient_end(&working)) {
+        add_number(ctx, &working[..end])?;
+        ctx.out.push(Token::Op(Ops::Mul));
+        ctx.out.push(Token::Var(working[end..].to_owned()));
         return Ok(());
     }
 
-    ctx.out
-        .push(Token::Number(match ctx.working.parse::<Num>() {
-            Ok(i) => i.copysign(if ctx.next_neg { -1. } else { 0. }),
-            Err(_) => return Err(Error::InvalidNumber(ctx.working.to_string())),
-        }));
+    if ctx.next_neg {
+        ctx.negate.push(ctx.out.len());
+    }
+    ctx.out.push(match dice {
+        Some(dice) if !dice.is_valid() => return Err(Error::InvalidDice(working)),
+        Some(dice) => Token::Dice(dice),
+        None => Token::Var(working),
+    });
     Ok(())
 }
 
+fn add_number(ctx: &mut TokenizeContext, number: &str) -> Result<()> {
+    if ctx.next_neg {
+        ctx.negative_numbers.push(ctx.out.len());
+    }
+    ctx.out.push(Token::Number(match number.parse::<Num>() {
+        Ok(i) => i.copysign(if ctx.next_neg { -1. } else { 0. }),
+        Err(_) => return Err(Error::InvalidNumber(number.to_owned())),
+    }));
+    Ok(())
+}
+
+// Where the number ends in a word like `2x`, if a name follows it
+fn coefficient_end(word: &str) -> Option<usize> {
+    let end = word.find(|x| !is_digit(x))?;
+    (end > 0 && word[end..].starts_with(|x: char| x.is_alphabetic() || x == '_')).then_some(end)
+}
+
 fn flush_working(ctx: &mut TokenizeContext) -> Result<()> {
     if !ctx.working.is_empty() {
         add_num(ctx)?;
@@ -121,18 +424,142 @@ fn add_op(op: Ops, ctx: &mut TokenizeContext) -> Result<()> {
     Ok(())
 }
 
+fn closes(open: char, close: char) -> bool {
+    matches!((open, close), ('(', ')') | ('[', ']'))
+}
+
+// Splits on the commas that are not inside a nested group
 fn tokenize_args(inp: &str) -> Result<Vec<Vec<Token>>> {
+    if inp.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
     let mut out = Vec::new();
-    for i in inp.split(',') {
-        out.push(tokenize(i)?);
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, chr) in inp.char_indices() {
+        match chr {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                out.push(tokenize(&inp[start..i])?);
+                start = i + 1;
+            }
+            _ => {}
+        }
     }
+    out.push(tokenize(&inp[start..])?);
 
     Ok(out)
 }
 
+fn add_word(inp: &str, range: Range<usize>, out: &mut Vec<Span>) {
+    let word = &inp[range.clone()];
+    let kind = if word.chars().all(is_digit) || is_scientific(word) {
+        match word.parse::<Num>() {
+            Ok(_) => SpanKind::Number,
+            Err(_) => SpanKind::Invalid,
+        }
+    } else {
+        match (Dice::parse(word), coefficient_end(word)) {
+            (Some(dice), _) if dice.is_valid() => SpanKind::Dice,
+            (Some(_), _) => SpanKind::Invalid,
+            (None, Some(end)) => {
+                add_word(inp, range.start..range.start + end, out);
+                add_word(inp, range.start + end..range.end, out);
+                return;
+            }
+            (None, None) => SpanKind::Ident {
+                call: inp[range.end..].trim_start().starts_with('('),
+                bound: false,
+            },
+        }
+    };
+
+    out.push(Span { kind, range });
+}
+
 fn var_name(token: &Token) -> Option<&str> {
     match token {
         Token::Var(i) => Some(i),
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::calc::{solver::Context, tree::create_tree};
+
+    fn eval(inp: &str, x: Num) -> Num {
+        let tree = create_tree(tokenize(inp).unwrap()).unwrap();
+        Context::new().evaluate_with(&tree, "x", x).unwrap()
+    }
+
+    #[test]
+    fn test_negated_power() {
+        assert_eq!(eval("-x^2", 3.), -9.);
+        assert_eq!(eval("-(x)^2", 3.), -9.);
+        assert_eq!(eval("-2^2", 0.), -4.);
+        assert_eq!(eval("1 - -x^2", 3.), 10.);
+    }
+
+    // Powers are applied before a unary minus, so `-2^2` is `-(2^2)`. It used to be `(-2)^2`.
+    #[test]
+    fn test_power_before_minus() {
+        assert_eq!(eval("-2^2", 0.), -4.);
+        assert_eq!(eval("2^-2", 0.), 0.25);
+        assert_eq!(eval("(-2)^2", 0.), 4.);
+    }
+
+    #[test]
+    fn test_negative_exponent() {
+        assert_eq!(eval("2^-1", 0.), 0.5);
+        assert_eq!(eval("2^-x", 2.), 0.25);
+        assert_eq!(eval("-2^-x", 1.), -0.5);
+    }
+
+    #[test]
+    fn test_coefficient() {
+        assert_eq!(eval("2x + 1", 3.), 7.);
+        assert_eq!(eval("-2x^2", 3.), -18.);
+        assert_eq!(eval("3pi", 0.), 3. * std::f64::consts::PI);
+        assert_eq!(eval("2sqrt(x)", 4.), 4.);
+    }
+
+    #[test]
+    fn test_scientific() {
+        assert_eq!(eval("1e-5", 0.), 1e-5);
+        assert_eq!(eval("2.5e3", 0.), 2500.);
+        assert_eq!(eval("1e+3 - x", 1.), 999.);
+        assert_eq!(eval("2e", 0.), 2. * std::f64::consts::E);
+        assert_eq!(eval("2e-x", 1.), 2. * std::f64::consts::E - 1.);
+    }
+
+    // Names in `inp` classified as bound
+    fn bound(inp: &str) -> Vec<&str> {
+        let takes_names = |name: &str| ["sum", "integrate", "linsolve"].contains(&name);
+        classify(inp, takes_names)
+            .into_iter()
+            .filter(|x| matches!(x.kind, SpanKind::Ident { bound: true, .. }))
+            .map(|x| &inp[x.range])
+            .collect()
+    }
+
+    #[test]
+    fn test_bound_names() {
+        assert_eq!(bound("sum(k^2, k, 1, 3)"), ["k", "k"]);
+        assert_eq!(bound("integrate(x^2, x, 0, 1) + x"), ["x", "x"]);
+        assert_eq!(bound("linsolve([2x + y = 3, x - y = 0], [x, y])").len(), 6);
+        assert_eq!(bound("a(n) = a(n - 1) + n, a(0) = n"), ["n", "n", "n"]);
+        assert_eq!(bound("sum(f(k, j), k, 1"), ["k", "k"]);
+        assert!(bound("sni(x) + max(y, z)").is_empty());
+    }
+
+    #[test]
+    fn test_negation() {
+        assert_eq!(eval("-x", 3.), -3.);
+        assert_eq!(eval("-x * 2", 3.), -6.);
+        assert_eq!(eval("--x^2", 3.), 9.);
+    }
+}
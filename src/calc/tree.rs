@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use super::{Error, Result, Token};
 
-// assumes no Trees in the input
+// assumes no Trees in the input, other than a single already built tree
 pub fn create_tree(mut tokens: Vec<Token>) -> Result<Token> {
     if tokens.len() == 1 {
         match tokens.pop().unwrap() {
@@ -10,6 +10,9 @@ pub fn create_tree(mut tokens: Vec<Token>) -> Result<Token> {
             Token::Group(i) => return create_tree(i),
             Token::Var(i) => return Ok(Token::Var(i)),
             Token::Func(i, j) => return Ok(Token::Func(i, j)),
+            Token::Dice(i) => return Ok(Token::Dice(i)),
+            i @ Token::List(_) => return i.make_tree(),
+            i @ (Token::Tree(..) | Token::Complex(_)) => return Ok(i),
             i => panic!("Invalid token in create_tree: {:?}", i),
         }
     }
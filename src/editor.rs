@@ -0,0 +1,214 @@
+//! Line editing for the REPL, with history kept between sessions, tab completion of
+//! functions, variables and commands, and highlighting of the input as it is typed.
+
+use std::{borrow::Cow, fs, path::PathBuf};
+
+use colored::Colorize;
+use rustyline::{
+    completion::{Completer, Pair},
+    highlight::Highlighter,
+    hint::Hinter,
+    history::DefaultHistory,
+    validate::Validator,
+    CompletionType, Config, EditMode, Editor,
+};
+
+use crate::{
+    calc::{
+        solver::Context,
+        tokens::{classify, SpanKind},
+    },
+    COMMANDS,
+};
+
+// Settings `:mode` takes, offered after it
+const MODES: &[&str] = &[
+    "rad", "deg", "grad", "smart", "fixed", "sig", "sci", "eng", "si", "frac", "sep", "nosep",
+];
+const MAX_HISTORY: usize = 1000;
+
+/// Names the editor completes, taken from the context before every line.
+pub struct Helper {
+    funcs: Vec<String>,
+    // Functions that take plain names as arguments, like `sum`
+    takes_names: Vec<String>,
+    sequences: Vec<String>,
+    vars: Vec<String>,
+}
+
+impl Helper {
+    fn new() -> Self {
+        Self {
+            funcs: Vec::new(),
+            takes_names: Vec::new(),
+            sequences: Vec::new(),
+            vars: Vec::new(),
+        }
+    }
+
+    /// Picks up functions and variables defined since the last line.
+    pub fn update(&mut self, context: &Context) {
+        self.funcs = sorted(context.funcs.keys());
+        self.takes_names = sorted(
+            context
+                .funcs
+                .iter()
+                .filter(|x| x.1.takes_names())
+                .map(|x| x.0),
+        );
+        self.sequences = sorted(context.sequences.keys());
+        self.vars = sorted(context.vars.keys());
+    }
+
+    fn is_func(&self, name: &str) -> bool {
+        self.funcs.iter().chain(&self.sequences).any(|x| x == name)
+    }
+
+    // What can go at the word being typed, given the text before it
+    fn candidates(&self, before: &str, word: &str) -> Vec<Pair> {
+        let pair = |display: &str, replacement: String| Pair {
+            display: display.to_owned(),
+            replacement,
+        };
+        let named = |names: &[String]| {
+            names
+                .iter()
+                .filter(|x| x.starts_with(word))
+                .map(|x| pair(x, x.to_owned()))
+                .collect::<Vec<_>>()
+        };
+
+        match before.trim() {
+            ":" => COMMANDS
+                .iter()
+                .filter_map(|x| x[1..].split_whitespace().next())
+                .filter(|x| x.starts_with(word))
+                .map(|x| pair(x, x.to_owned()))
+                .collect(),
+            ":help" => named(&self.funcs),
+            ":del" => {
+                let mut out = named(&self.vars);
+                out.extend(named(&self.sequences));
+                out
+            }
+            ":mode" => MODES
+                .iter()
+                .filter(|x| x.starts_with(word))
+                .map(|x| pair(x, x.to_string()))
+                .collect(),
+            i if i.starts_with(':') => Vec::new(),
+            // Functions get their opening parenthesis
+            _ => {
+                let mut out = self
+                    .funcs
+                    .iter()
+                    .chain(&self.sequences)
+                    .filter(|x| x.starts_with(word))
+                    .map(|x| pair(x, format!("{}(", x)))
+                    .collect::<Vec<_>>();
+                out.extend(named(&self.vars));
+                out
+            }
+        }
+    }
+}
+
+impl Completer for Helper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let start = line[..pos]
+            .char_indices()
+            .rev()
+            .find(|(_, x)| !is_ident(*x))
+            .map(|(i, x)| i + x.len_utf8())
+            .unwrap_or(0);
+        Ok((start, self.candidates(&line[..start], &line[start..pos])))
+    }
+}
+
+impl Hinter for Helper {
+    type Hint = String;
+}
+
+// Numbers are cyan, dice yellow, functions blue and variables green, along with names a call
+// binds like `k` in `sum(k^2, k, 1, 3)`. Unknown names are red, and so are brackets without a
+// partner.
+impl Highlighter for Helper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        // Commands are not expressions
+        if line.trim_start().starts_with(':') {
+            return Cow::Borrowed(line);
+        }
+
+        let mut out = String::new();
+        let mut last = 0;
+        for span in classify(line, |x| self.takes_names.iter().any(|i| i == x)) {
+            out.push_str(&line[last..span.range.start]);
+            let text = &line[span.range.clone()];
+            let name = text.to_lowercase();
+            let colored = match span.kind {
+                SpanKind::Number => text.cyan(),
+                SpanKind::Dice => text.yellow(),
+                SpanKind::Ident { call: true, .. } if self.is_func(&name) => text.blue(),
+                SpanKind::Ident { call: false, bound } if bound || self.vars.contains(&name) => {
+                    text.green()
+                }
+                SpanKind::Operator | SpanKind::Comma => text.normal(),
+                SpanKind::Bracket { matched: true } => text.bold(),
+                SpanKind::Ident { .. } | SpanKind::Bracket { .. } | SpanKind::Invalid => text.red(),
+            };
+            out.push_str(&colored.to_string());
+            last = span.range.end;
+        }
+        out.push_str(&line[last..]);
+
+        Cow::Owned(out)
+    }
+
+    // Anything typed can change how the rest of the line is coloured, like closing a bracket
+    fn highlight_char(&self, _line: &str, _pos: usize, _forced: bool) -> bool {
+        true
+    }
+}
+
+impl Validator for Helper {}
+
+impl rustyline::Helper for Helper {}
+
+/// Makes an editor with emacs keybindings, or vi ones if `vi` is set.
+pub fn editor(vi: bool) -> rustyline::Result<Editor<Helper, DefaultHistory>> {
+    let config = Config::builder()
+        .edit_mode(if vi { EditMode::Vi } else { EditMode::Emacs })
+        .completion_type(CompletionType::List)
+        .max_history_size(MAX_HISTORY)?
+        .auto_add_history(true)
+        .build();
+
+    let mut editor = Editor::with_config(config)?;
+    editor.set_helper(Some(Helper::new()));
+    Ok(editor)
+}
+
+/// Where history is kept between sessions, in the XDG data directory on Linux. The directory
+/// is created if needed.
+pub fn history_path() -> Option<PathBuf> {
+    let dir = dirs::data_dir()?.join("calc");
+    fs::create_dir_all(&dir).ok()?;
+    Some(dir.join("history"))
+}
+
+fn sorted<'a>(names: impl Iterator<Item = &'a String>) -> Vec<String> {
+    let mut out = names.cloned().collect::<Vec<_>>();
+    out.sort_unstable();
+    out
+}
+
+fn is_ident(chr: char) -> bool {
+    chr.is_alphanumeric() || chr == '_'
+}
@@ -1,4 +1,6 @@
-use crate::calc::{solver::Context, Num, Result, Token};
+use crate::calc::{
+    angle::AngleMode, dual::Dual, number::Number, solver::Context, Num, Result, Token,
+};
 
 use super::{reqire_args, Function};
 
@@ -6,14 +8,103 @@ macro_rules! basic_func {
     ($name:ident, $func:ident) => {
         pub struct $name;
 
+        impl $name {
+            fn apply<N: Number>(x: N) -> N {
+                x.$func()
+            }
+        }
+
         impl Function for $name {
             fn name(&self) -> &'static str {
                 stringify!($name)
             }
 
+            fn is_pure(&self) -> bool {
+                true
+            }
+
             fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
                 reqire_args(self.name(), &args, 1)?;
-                Ok(context.evaluate(args[0].to_owned())?.$func())
+                Ok(Self::apply(context.evaluate(args[0].to_owned())?))
+            }
+
+            fn call_dual(&self, args: Vec<Dual>, _context: &mut Context) -> Result<Dual> {
+                reqire_args(self.name(), &args, 1)?;
+                Ok(Self::apply(args[0]))
+            }
+        }
+    };
+}
+
+// Trigonometric functions take their angle in the current angle mode
+macro_rules! trig_func {
+    ($name:ident, $func:ident, $exact:expr) => {
+        pub struct $name;
+
+        impl $name {
+            fn apply<N: Number>(x: N, angle: AngleMode) -> N {
+                angle.to_radians(x).$func()
+            }
+        }
+
+        impl Function for $name {
+            fn name(&self) -> &'static str {
+                stringify!($name)
+            }
+
+            fn is_pure(&self) -> bool {
+                true
+            }
+
+            fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+                reqire_args(self.name(), &args, 1)?;
+                let x = context.evaluate(args[0].to_owned())?;
+
+                // So `sin(180)` is 0 in degrees, rather than off by the rounding of pi
+                if let Some(i) = context.angle().quarter_turns(x) {
+                    let exact: [Num; 4] = $exact;
+                    return Ok(exact[i]);
+                }
+                Ok(Self::apply(x, context.angle()))
+            }
+
+            fn call_dual(&self, args: Vec<Dual>, context: &mut Context) -> Result<Dual> {
+                reqire_args(self.name(), &args, 1)?;
+                Ok(Self::apply(args[0], context.angle()))
+            }
+        }
+    };
+}
+
+// Inverse trigonometric functions give their angle in the current angle mode
+macro_rules! inverse_trig_func {
+    ($name:ident, $func:ident) => {
+        pub struct $name;
+
+        impl $name {
+            fn apply<N: Number>(x: N, angle: AngleMode) -> N {
+                angle.convert_radians(x.$func())
+            }
+        }
+
+        impl Function for $name {
+            fn name(&self) -> &'static str {
+                stringify!($name)
+            }
+
+            fn is_pure(&self) -> bool {
+                true
+            }
+
+            fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+                reqire_args(self.name(), &args, 1)?;
+                let x = context.evaluate(args[0].to_owned())?;
+                Ok(Self::apply(x, context.angle()))
+            }
+
+            fn call_dual(&self, args: Vec<Dual>, context: &mut Context) -> Result<Dual> {
+                reqire_args(self.name(), &args, 1)?;
+                Ok(Self::apply(args[0], context.angle()))
             }
         }
     };
@@ -34,12 +125,12 @@ basic_func!(Cbrt, cbrt);
 basic_func!(ExpM1, exp_m1);
 basic_func!(Ln1p, ln_1p);
 basic_func!(Sqrt, sqrt);
-basic_func!(Sin, sin);
-basic_func!(Cos, cos);
-basic_func!(Tan, tan);
-basic_func!(Asin, asin);
-basic_func!(Acos, acos);
-basic_func!(Atan, atan);
+trig_func!(Sin, sin, [0., 1., 0., -1.]);
+trig_func!(Cos, cos, [1., 0., -1., 0.]);
+trig_func!(Tan, tan, [0., Num::INFINITY, 0., Num::INFINITY]);
+inverse_trig_func!(Asin, asin);
+inverse_trig_func!(Acos, acos);
+inverse_trig_func!(Atan, atan);
 basic_func!(Sinh, sinh);
 basic_func!(Cosh, cosh);
 basic_func!(Tanh, tanh);
@@ -49,4 +140,33 @@ basic_func!(Atanh, atanh);
 basic_func!(Recip, recip);
 basic_func!(ToDegrees, to_degrees);
 basic_func!(ToRadians, to_radians);
-basic_func!(SigNum, signum);
\ No newline at end of file
+basic_func!(SigNum, signum);
+
+/// An angle in degrees, in the current angle mode. This is what `30°` turns into.
+pub struct Deg;
+impl Deg {
+    fn apply<N: Number>(x: N, angle: AngleMode) -> N {
+        angle.convert_degrees(x)
+    }
+}
+
+impl Function for Deg {
+    fn name(&self) -> &'static str {
+        "deg"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        reqire_args(self.name(), &args, 1)?;
+        let x = context.evaluate(args[0].to_owned())?;
+        Ok(Self::apply(x, context.angle()))
+    }
+
+    fn call_dual(&self, args: Vec<Dual>, context: &mut Context) -> Result<Dual> {
+        reqire_args(self.name(), &args, 1)?;
+        Ok(Self::apply(args[0], context.angle()))
+    }
+}
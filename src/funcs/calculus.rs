@@ -0,0 +1,503 @@
+//! Numerical integration, differentiation and limits.
+
+use super::{
+    reqire_args, reqire_args_range,
+    symbolic::{differentiate, expression},
+    var_arg, Function,
+};
+use crate::calc::{
+    dual::Dual, simplify::simplify, solver::Context, Error, Num, Ops, Result, Token,
+};
+
+// Default absolute and relative tolerance of `integrate`
+const TOLERANCE: Num = 1e-10;
+const MAX_INTERVALS: usize = 2000;
+
+// Gauss-Kronrod 7-15 nodes and weights on [-1, 1], only the non-negative half
+const KRONROD_NODES: [Num; 8] = [
+    0.991_455_371_120_812_6,
+    0.949_107_912_342_758_5,
+    0.864_864_423_359_769_1,
+    0.741_531_185_599_394_4,
+    0.586_087_235_467_691_1,
+    0.405_845_151_377_397_2,
+    0.207_784_955_007_898_5,
+    0.,
+];
+const KRONROD_WEIGHTS: [Num; 8] = [
+    0.022_935_322_010_529_22,
+    0.063_092_092_629_978_55,
+    0.104_790_010_322_250_2,
+    0.140_653_259_715_525_9,
+    0.169_004_726_639_267_9,
+    0.190_350_578_064_785_4,
+    0.204_432_940_075_298_9,
+    0.209_482_141_084_727_8,
+];
+// Gauss weights for the odd Kronrod nodes
+const GAUSS_WEIGHTS: [Num; 4] = [
+    0.129_484_966_168_869_7,
+    0.279_705_391_489_276_7,
+    0.381_830_050_505_118_9,
+    0.417_959_183_673_469_4,
+];
+
+// Steps in Ridders' extrapolation table
+const RIDDERS_STEPS: usize = 10;
+const RIDDERS_SHRINK: Num = 1.4;
+
+// Times L'Hôpital's rule is applied before falling back to extrapolation
+const MAX_LHOPITAL: usize = 5;
+// Points approaching the limit, each twice as close as the last
+const LIMIT_STEPS: usize = 12;
+// Relative difference allowed between the left and right limits
+const LIMIT_TOLERANCE: Num = 1e-6;
+
+pub struct Integrate;
+impl Function for Integrate {
+    fn name(&self) -> &'static str {
+        "integrate"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    // (expr, var, a, b, [tolerance])
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        reqire_args_range(self.name(), &args, 4, 5)?;
+        let var = var_arg(self.name(), &args[1])?;
+        let a = context.evaluate(args[2].to_owned())?;
+        let b = context.evaluate(args[3].to_owned())?;
+        let tol = match args.get(4) {
+            Some(i) => context.evaluate(i.to_owned())?,
+            None => TOLERANCE,
+        };
+
+        let (value, error) = integrate(a, b, tol, |x| context.evaluate_with(&args[0], &var, x))?;
+        if !converged(value, error, tol) {
+            return Err(Error::NoConvergence(self.name().to_owned()));
+        }
+
+        Ok(value)
+    }
+}
+
+pub struct Deriv;
+impl Function for Deriv {
+    fn name(&self) -> &'static str {
+        "deriv"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    // (expr, var, at)
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        reqire_args(self.name(), &args, 3)?;
+        let var = var_arg(self.name(), &args[1])?;
+        let at = context.evaluate(args[2].to_owned())?;
+        Ok(derivative(at, |x| context.evaluate_with(&args[0], &var, x))?.0)
+    }
+}
+
+pub struct Limit;
+impl Function for Limit {
+    fn name(&self) -> &'static str {
+        "limit"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    // (expr, var, at, [side]), a negative side only approaches from below and a positive one
+    // only from above
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        reqire_args_range(self.name(), &args, 3, 4)?;
+        let var = var_arg(self.name(), &args[1])?;
+        let at = context.evaluate(args[2].to_owned())?;
+        let side = match args.get(3) {
+            Some(i) => context.evaluate(i.to_owned())?.signum(),
+            None => 0.,
+        };
+
+        let expr = expression(&args[0], context)?;
+        limit(&expr, &var, at, side, context)
+    }
+}
+
+pub struct Grad;
+impl Function for Grad {
+    fn name(&self) -> &'static str {
+        "grad"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    // (expr, [vars], [at])
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args(self.name(), &args, 3)?;
+        let rows = jacobian(
+            self.name(),
+            &[args[0].to_owned()],
+            &args[1],
+            &args[2],
+            context,
+        )?;
+        Ok(Token::List(
+            rows[0].iter().map(|x| Token::Number(*x)).collect(),
+        ))
+    }
+}
+
+pub struct Jacobian;
+impl Function for Jacobian {
+    fn name(&self) -> &'static str {
+        "jacobian"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    // ([exprs], [vars], [at]), returns a row for each expression
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args(self.name(), &args, 3)?;
+        let exprs = match &args[0] {
+            Token::List(items) => items.to_owned(),
+            i => vec![i.to_owned()],
+        };
+        let rows = jacobian(self.name(), &exprs, &args[1], &args[2], context)?;
+        Ok(Token::List(
+            rows.into_iter()
+                .map(|x| Token::List(x.into_iter().map(Token::Number).collect()))
+                .collect(),
+        ))
+    }
+}
+
+/// Adaptive Gauss-Kronrod integration, returns the value and an error estimate.
+/// Infinite bounds are mapped onto finite intervals.
+pub fn integrate(
+    a: Num,
+    b: Num,
+    tol: Num,
+    mut f: impl FnMut(Num) -> Result<Num>,
+) -> Result<(Num, Num)> {
+    if a == b {
+        return Ok((0., 0.));
+    }
+
+    if a > b {
+        let (value, error) = integrate(b, a, tol, f)?;
+        return Ok((-value, error));
+    }
+
+    match (a.is_finite(), b.is_finite()) {
+        (true, true) => adaptive(a, b, tol, f),
+        (false, false) => adaptive(-1., 1., tol, |t| {
+            let d = 1. - t * t;
+            Ok(f(t / d)? * (1. + t * t) / (d * d))
+        }),
+        (true, false) => adaptive(0., 1., tol, |t| {
+            let d = 1. - t;
+            Ok(f(a + t / d)? / (d * d))
+        }),
+        (false, true) => adaptive(0., 1., tol, |t| Ok(f(b - (1. - t) / t)? / (t * t))),
+    }
+}
+
+/// Whether an `integrate` result is within the tolerance.
+pub fn converged(value: Num, error: Num, tol: Num) -> bool {
+    value.is_finite() && error <= tol.max(tol * value.abs())
+}
+
+// Repeatedly splits the interval with the largest error until the total is small enough
+fn adaptive(a: Num, b: Num, tol: Num, mut f: impl FnMut(Num) -> Result<Num>) -> Result<(Num, Num)> {
+    let mut intervals = vec![(a, b, kronrod(a, b, &mut f)?)];
+    loop {
+        let value = intervals.iter().map(|x| x.2 .0).sum::<Num>();
+        let error = intervals.iter().map(|x| x.2 .1).sum::<Num>();
+        if converged(value, error, tol) || intervals.len() >= MAX_INTERVALS {
+            return Ok((value, error));
+        }
+
+        let worst = intervals
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1 .2 .1.total_cmp(&b.1 .2 .1))
+            .unwrap()
+            .0;
+        let (a, b, _) = intervals.swap_remove(worst);
+        let mid = a + (b - a) / 2.;
+        if mid <= a || mid >= b {
+            return Ok((value, error));
+        }
+
+        intervals.push((a, mid, kronrod(a, mid, &mut f)?));
+        intervals.push((mid, b, kronrod(mid, b, &mut f)?));
+    }
+}
+
+// Single 15 point Kronrod rule, the difference to the embedded 7 point Gauss rule is the error
+fn kronrod(a: Num, b: Num, f: &mut impl FnMut(Num) -> Result<Num>) -> Result<(Num, Num)> {
+    let center = (a + b) / 2.;
+    let half = (b - a) / 2.;
+
+    let mid = f(center)?;
+    let mut kronrod = mid * KRONROD_WEIGHTS[7];
+    let mut gauss = mid * GAUSS_WEIGHTS[3];
+    for i in 0..7 {
+        let dx = half * KRONROD_NODES[i];
+        let sum = f(center - dx)? + f(center + dx)?;
+        kronrod += KRONROD_WEIGHTS[i] * sum;
+        if i % 2 == 1 {
+            gauss += GAUSS_WEIGHTS[i / 2] * sum;
+        }
+    }
+
+    Ok((kronrod * half, ((kronrod - gauss) * half).abs()))
+}
+
+/// Ridders' method, central differences extrapolated to a step of zero.
+/// Returns the derivative and an error estimate.
+pub fn derivative(x: Num, mut f: impl FnMut(Num) -> Result<Num>) -> Result<(Num, Num)> {
+    let shrink2 = RIDDERS_SHRINK * RIDDERS_SHRINK;
+    let mut h = 0.1 * x.abs().max(1.);
+    let mut table = vec![vec![0.; RIDDERS_STEPS]; RIDDERS_STEPS];
+    table[0][0] = (f(x + h)? - f(x - h)?) / (2. * h);
+
+    let mut best = (table[0][0], Num::INFINITY);
+    for i in 1..RIDDERS_STEPS {
+        h /= RIDDERS_SHRINK;
+        table[0][i] = (f(x + h)? - f(x - h)?) / (2. * h);
+
+        let mut factor = shrink2;
+        for j in 1..=i {
+            table[j][i] = (table[j - 1][i] * factor - table[j - 1][i - 1]) / (factor - 1.);
+            factor *= shrink2;
+
+            let error = (table[j][i] - table[j - 1][i])
+                .abs()
+                .max((table[j][i] - table[j - 1][i - 1]).abs());
+            if error <= best.1 {
+                best = (table[j][i], error);
+            }
+        }
+
+        // Stop once higher orders start making things worse
+        if (table[i][i] - table[i - 1][i - 1]).abs() >= 2. * best.1 {
+            break;
+        }
+    }
+
+    Ok(best)
+}
+
+/// Partial derivatives of every expression with respect to every variable at a point, found by
+/// evaluating with dual numbers once per variable. They are exact except through functions
+/// that only have a numeric derivative.
+fn jacobian(
+    name: &str,
+    exprs: &[Token],
+    vars: &Token,
+    at: &Token,
+    context: &mut Context,
+) -> Result<Vec<Vec<Num>>> {
+    let (vars, at) = match (vars, at) {
+        (Token::List(vars), Token::List(at)) if vars.len() == at.len() => (vars.to_owned(), at),
+        (Token::List(_), _) => {
+            return Err(Error::InvalidArgument(
+                name.to_owned(),
+                "expected a point with a value for every variable".to_owned(),
+            ))
+        }
+        (var, at) => (vec![var.to_owned()], &vec![at.to_owned()]),
+    };
+    let vars = vars
+        .iter()
+        .map(|x| var_arg(name, x))
+        .collect::<Result<Vec<_>>>()?;
+    let at = at
+        .iter()
+        .map(|x| context.evaluate(x.to_owned()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut rows = vec![Vec::new(); exprs.len()];
+    for i in 0..vars.len() {
+        let bindings = vars
+            .iter()
+            .zip(at.iter())
+            .enumerate()
+            .map(|(j, (var, x))| (var.as_str(), Dual::new(*x, (i == j) as u8 as Num)))
+            .collect::<Vec<_>>();
+        for (row, expr) in rows.iter_mut().zip(exprs) {
+            row.push(context.evaluate_as(expr.to_owned(), &bindings)?.eps);
+        }
+    }
+
+    Ok(rows)
+}
+
+/// Limit of `expr` as `var` approaches `at`, from one side if `side` is not zero.
+/// Tries evaluating directly, then L'Hôpital's rule for quotients, then extrapolates from
+/// points approaching `at`.
+pub fn limit(expr: &Token, var: &str, at: Num, side: Num, context: &mut Context) -> Result<Num> {
+    if at.is_finite() && side == 0. {
+        let value = context.evaluate_with(expr, var, at)?;
+        if value.is_finite() {
+            return Ok(value);
+        }
+    }
+
+    let sides: &[Num] = match (side, at) {
+        (_, Num::INFINITY) => &[-1.],
+        (_, Num::NEG_INFINITY) => &[1.],
+        (0., _) => &[-1., 1.],
+        (side, _) => &[side],
+    };
+
+    let values = sides
+        .iter()
+        .map(|side| one_sided(expr, var, at, *side, context))
+        .collect::<Result<Vec<_>>>()?;
+    let (low, high) = (values[0], values[values.len() - 1]);
+    let close = |a: Num, b: Num, tol: Num| {
+        a == b || a.is_finite() && (a - b).abs() <= tol * a.abs().max(1.)
+    };
+    if !close(low, high, LIMIT_TOLERANCE) {
+        return Err(Error::InvalidArgument(
+            "limit".to_owned(),
+            format!("the limit from below ({}) and above ({}) differ", low, high),
+        ));
+    }
+
+    // L'Hôpital's rule is exact, but assumes both sides are differentiable, like `abs(x) / x`
+    // at 0 is not, so it is only used to refine the extrapolated value
+    let value = (low + high) / 2.;
+    if at.is_finite() {
+        if let Some(exact) = lhopital(expr, var, at, context)? {
+            if close(exact, value, LIMIT_TOLERANCE.sqrt()) {
+                return Ok(exact);
+            }
+        }
+    }
+
+    Ok(value)
+}
+
+// Differentiates the top and bottom of a quotient while both are zero or both are infinite
+fn lhopital(expr: &Token, var: &str, at: Num, context: &mut Context) -> Result<Option<Num>> {
+    let (mut top, mut bottom) = match expr {
+        Token::Tree(Ops::Div, top, bottom) => (*top.to_owned(), *bottom.to_owned()),
+        _ => return Ok(None),
+    };
+
+    for _ in 0..MAX_LHOPITAL {
+        let t = context.evaluate_with(&top, var, at)?;
+        let b = context.evaluate_with(&bottom, var, at)?;
+        if t.is_finite() && b.is_finite() && b != 0. {
+            return Ok(Some(t / b));
+        }
+
+        if !(t == 0. && b == 0. || t.is_infinite() && b.is_infinite()) {
+            return Ok(None);
+        }
+
+        let derivative = differentiate(&top, var, context)?;
+        top = simplify(derivative, context);
+        let derivative = differentiate(&bottom, var, context)?;
+        bottom = simplify(derivative, context);
+    }
+
+    Ok(None)
+}
+
+// Richardson extrapolation of f(at + side * h) to h = 0, infinite points use f(side / h)
+fn one_sided(expr: &Token, var: &str, at: Num, side: Num, context: &mut Context) -> Result<Num> {
+    let mut f = |h: Num| {
+        let x = match at.is_finite() {
+            true => at + side * h,
+            false => -side / h,
+        };
+        context.evaluate_with(expr, var, x)
+    };
+
+    let mut h = 0.1 * if at.is_finite() { at.abs().max(1.) } else { 1. };
+    let mut table = vec![vec![0.; LIMIT_STEPS]; LIMIT_STEPS];
+    table[0][0] = f(h)?;
+
+    let mut best = (table[0][0], Num::INFINITY);
+    let mut growing = 0;
+    let mut converging = false;
+    for i in 1..LIMIT_STEPS {
+        h /= 2.;
+        table[0][i] = f(h)?;
+
+        // Values that keep doubling in size are heading off to infinity
+        let (prev, cur) = (table[0][i - 1], table[0][i]);
+        match cur.signum() == prev.signum() && cur.abs() >= 1.5 * prev.abs() {
+            true => growing += 1,
+            false => growing = 0,
+        }
+        if cur.is_infinite() || growing >= LIMIT_STEPS / 2 {
+            return Ok(Num::INFINITY.copysign(cur));
+        }
+
+        let mut factor = 2.;
+        for j in 1..=i {
+            table[j][i] = (table[j - 1][i] * factor - table[j - 1][i - 1]) / (factor - 1.);
+            factor *= 2.;
+
+            let error = (table[j][i] - table[j - 1][i])
+                .abs()
+                .max((table[j][i] - table[j - 1][i - 1]).abs());
+            if error <= best.1 {
+                best = (table[j][i], error);
+            }
+        }
+
+        converging |= best.1 <= LIMIT_TOLERANCE * best.0.abs().max(1.);
+        if converging && (table[i][i] - table[i - 1][i - 1]).abs() >= 2. * best.1 {
+            break;
+        }
+    }
+
+    if best.1 > LIMIT_TOLERANCE.sqrt() * best.0.abs().max(1.) {
+        return Err(Error::NoConvergence("limit".to_owned()));
+    }
+
+    Ok(best.0)
+}
@@ -0,0 +1,74 @@
+use std::collections::BTreeMap;
+
+use super::{reqire_args, Function};
+use crate::calc::{dice::convolve, solver::Context, Error, Num, Ops, Result, Token};
+
+const BAR_WIDTH: f64 = 40.;
+
+pub struct Dist;
+impl Function for Dist {
+    fn name(&self) -> &'static str {
+        "dist"
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        reqire_args(self.name(), &args, 1)?;
+        let dist = distribution(&args[0], context)?;
+
+        let max = dist.values().cloned().fold(0., Num::max);
+        let width = dist.keys().map(|x| x.to_string().len()).max().unwrap_or(0);
+        println!(" [DIST] {}", args[0]);
+        for (value, prob) in &dist {
+            println!(
+                "  {:>width$}  {:>6.2}%  {}",
+                value,
+                prob * 100.,
+                "█".repeat((prob / max * BAR_WIDTH).round() as usize),
+                width = width
+            );
+        }
+
+        Ok(dist.iter().map(|(v, p)| *v as Num * p).sum())
+    }
+}
+
+// Exact distribution of a dice expression made of dice, integers, `+`, `-` and `*` by a constant
+fn distribution(token: &Token, context: &mut Context) -> Result<BTreeMap<i64, f64>> {
+    let err = || {
+        Error::InvalidArgument(
+            "dist".to_owned(),
+            format!("`{}` is not a dice expression", token),
+        )
+    };
+
+    Ok(match token {
+        Token::Dice(dice) => dice.distribution()?,
+        Token::Number(n) if n.fract() == 0. => BTreeMap::from([(*n as i64, 1.)]),
+        Token::Var(name) => match context.get_var(name).cloned() {
+            Some(i) => distribution(&i, context)?,
+            None => return Err(Error::UnknownIdentifier(token.to_string())),
+        },
+        Token::Tree(op, left, right) => {
+            let left = distribution(left, context)?;
+            let right = distribution(right, context)?;
+            match op {
+                Ops::Add => convolve(&left, &right),
+                Ops::Sub => convolve(&left, &right.into_iter().map(|(v, p)| (-v, p)).collect()),
+                Ops::Mul => match (constant(&left), constant(&right)) {
+                    (Some(c), _) => right.into_iter().map(|(v, p)| (v * c, p)).collect(),
+                    (_, Some(c)) => left.into_iter().map(|(v, p)| (v * c, p)).collect(),
+                    _ => return Err(err()),
+                },
+                _ => return Err(err()),
+            }
+        }
+        _ => return Err(err()),
+    })
+}
+
+fn constant(dist: &BTreeMap<i64, f64>) -> Option<i64> {
+    match dist.len() {
+        1 => dist.keys().next().copied(),
+        _ => None,
+    }
+}
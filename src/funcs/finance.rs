@@ -0,0 +1,301 @@
+//! Financial functions.
+//!
+//! These follow the usual spreadsheet conventions: money paid out is negative, money received
+//! is positive and the optional `type` argument is 0 for payments at the end of each period or
+//! 1 for payments at the start.
+
+use super::{eval_list, reqire_args, reqire_args_range, Function};
+use crate::calc::{solver::Context, Error, Num, Result, Token};
+
+const EPSILON: Num = 1e-12;
+const MAX_ITER: usize = 100;
+
+macro_rules! finance_func {
+    ($name:ident, $str:expr, $min:expr, $defaults:expr, $func:ident) => {
+        pub struct $name;
+
+        impl Function for $name {
+            fn name(&self) -> &'static str {
+                $str
+            }
+
+            fn is_pure(&self) -> bool {
+                true
+            }
+
+            fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+                let defaults: &[Num] = &$defaults;
+                reqire_args_range(self.name(), &args, $min, $min + defaults.len())?;
+
+                let mut args = args
+                    .into_iter()
+                    .map(|x| context.evaluate(x))
+                    .collect::<Result<Vec<_>>>()?;
+                args.extend(&defaults[args.len() - $min..]);
+                $func(self.name(), &args)
+            }
+        }
+    };
+}
+
+// (rate, nper, pv, [fv, type])
+finance_func!(Pmt, "pmt", 3, [0., 0.], pmt);
+// (rate, nper, pmt, [fv, type])
+finance_func!(Pv, "pv", 3, [0., 0.], pv);
+// (rate, nper, pmt, [pv, type])
+finance_func!(Fv, "fv", 3, [0., 0.], fv);
+// (rate, pmt, pv, [fv, type])
+finance_func!(Nper, "nper", 3, [0., 0.], nper);
+// (nper, pmt, pv, [fv, type, guess])
+finance_func!(Rate, "rate", 3, [0., 0., 0.1], rate);
+// (principal, rate, compounds per period, periods)
+finance_func!(Compound, "compound", 4, [], compound);
+// (nominal rate, compounds per year)
+finance_func!(Effect, "effect", 2, [], effect);
+// (effective rate, compounds per year)
+finance_func!(Nominal, "nominal", 2, [], nominal);
+
+pub struct Npv;
+impl Function for Npv {
+    fn name(&self) -> &'static str {
+        "npv"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    // (rate, cash flows...), the first cash flow is at the end of the first period
+    fn call(&self, mut args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        reqire_args_range(self.name(), &args, 2, usize::MAX)?;
+        let rate = context.evaluate(args.remove(0))?;
+        let flows = eval_list(args, context)?;
+        Ok(net_present_value(rate, &flows) / (1. + rate))
+    }
+}
+
+pub struct Irr;
+impl Function for Irr {
+    fn name(&self) -> &'static str {
+        "irr"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    // (cash flows...), the first cash flow is at time zero
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        let flows = eval_list(args, context)?;
+        if !flows.iter().any(|x| *x > 0.) || !flows.iter().any(|x| *x < 0.) {
+            return Err(Error::InvalidArgument(
+                self.name().to_owned(),
+                "cash flows need both a positive and a negative value".to_owned(),
+            ));
+        }
+
+        let f = |r: Num| net_present_value(r, &flows);
+        let df = |r: Num| {
+            flows
+                .iter()
+                .enumerate()
+                .map(|(i, c)| -(i as Num) * c / (1. + r).powi(i as i32 + 1))
+                .sum::<Num>()
+        };
+
+        newton(0.1, f, df)
+            .or_else(|| bisect(f, -1. + EPSILON, 1e3))
+            .ok_or_else(|| Error::NoConvergence(self.name().to_owned()))
+    }
+}
+
+pub struct Amort;
+impl Function for Amort {
+    fn name(&self) -> &'static str {
+        "amort"
+    }
+
+    // (rate, nper, principal), prints the schedule and returns the total interest paid
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        reqire_args(self.name(), &args, 3)?;
+        let rate = context.evaluate(args[0].to_owned())?;
+        let periods = context.evaluate(args[1].to_owned())?;
+        let mut balance = context.evaluate(args[2].to_owned())?;
+        if periods < 1. || periods.fract() != 0. {
+            return Err(Error::InvalidArgument(
+                self.name().to_owned(),
+                "number of periods must be a positive integer".to_owned(),
+            ));
+        }
+
+        let payment = -pmt(self.name(), &[rate, periods, balance, 0., 0.])?;
+        let mut total = 0.;
+        println!(
+            " [AMORT] {:>6} {:>14} {:>14} {:>14} {:>14}",
+            "Period", "Payment", "Interest", "Principal", "Balance"
+        );
+        for period in 1..=periods as usize {
+            let interest = balance * rate;
+            let principal = payment - interest;
+            balance -= principal;
+            total += interest;
+            println!(
+                "         {:>6} {:>14.2} {:>14.2} {:>14.2} {:>14.2}",
+                period,
+                payment,
+                interest,
+                principal,
+                // Avoid printing rounding error as `-0.00`
+                if balance.abs() < EPSILON.sqrt() {
+                    0.
+                } else {
+                    balance
+                }
+            );
+        }
+
+        Ok(total)
+    }
+}
+
+// (1 + r)^n, with the annuity factor ((1 + r)^n - 1) / r
+fn growth(rate: Num, nper: Num) -> (Num, Num) {
+    let growth = (1. + rate).powf(nper);
+    let annuity = match rate {
+        0. => nper,
+        r => (growth - 1.) / r,
+    };
+
+    (growth, annuity)
+}
+
+fn pmt(name: &str, a: &[Num]) -> Result<Num> {
+    let (rate, nper, pv, fv, kind) = (a[0], a[1], a[2], a[3], a[4]);
+    check_nper(name, nper)?;
+    let (growth, annuity) = growth(rate, nper);
+    Ok(-(pv * growth + fv) / ((1. + rate * kind) * annuity))
+}
+
+fn pv(name: &str, a: &[Num]) -> Result<Num> {
+    let (rate, nper, pmt, fv, kind) = (a[0], a[1], a[2], a[3], a[4]);
+    check_nper(name, nper)?;
+    let (growth, annuity) = growth(rate, nper);
+    Ok(-(fv + pmt * (1. + rate * kind) * annuity) / growth)
+}
+
+fn fv(_: &str, a: &[Num]) -> Result<Num> {
+    let (rate, nper, pmt, pv, kind) = (a[0], a[1], a[2], a[3], a[4]);
+    let (growth, annuity) = growth(rate, nper);
+    Ok(-(pv * growth + pmt * (1. + rate * kind) * annuity))
+}
+
+fn nper(name: &str, a: &[Num]) -> Result<Num> {
+    let (rate, pmt, pv, fv, kind) = (a[0], a[1], a[2], a[3], a[4]);
+    if rate == 0. {
+        return Ok(-(pv + fv) / pmt);
+    }
+
+    let pmt = pmt * (1. + rate * kind);
+    let out = ((pmt - fv * rate) / (pmt + pv * rate)).ln() / rate.ln_1p();
+    if !out.is_finite() {
+        return Err(Error::InvalidArgument(
+            name.to_owned(),
+            "the payments never reach the future value".to_owned(),
+        ));
+    }
+
+    Ok(out)
+}
+
+fn rate(name: &str, a: &[Num]) -> Result<Num> {
+    let (nper, pmt, pv, fv, kind, guess) = (a[0], a[1], a[2], a[3], a[4], a[5]);
+    let f = |r: Num| {
+        let (growth, annuity) = growth(r, nper);
+        pv * growth + pmt * (1. + r * kind) * annuity + fv
+    };
+    let df = |r: Num| {
+        let h = (r.abs() * 1e-6).max(1e-9);
+        (f(r + h) - f(r - h)) / (2. * h)
+    };
+
+    newton(guess, f, df).ok_or_else(|| Error::NoConvergence(name.to_owned()))
+}
+
+fn compound(_: &str, a: &[Num]) -> Result<Num> {
+    let (principal, rate, n, t) = (a[0], a[1], a[2], a[3]);
+    Ok(principal * (1. + rate / n).powf(n * t))
+}
+
+fn effect(name: &str, a: &[Num]) -> Result<Num> {
+    check_periods(name, a[1])?;
+    Ok((1. + a[0] / a[1]).powf(a[1]) - 1.)
+}
+
+fn nominal(name: &str, a: &[Num]) -> Result<Num> {
+    check_periods(name, a[1])?;
+    Ok(a[1] * ((1. + a[0]).powf(1. / a[1]) - 1.))
+}
+
+fn check_periods(name: &str, n: Num) -> Result<()> {
+    if n < 1. {
+        return Err(Error::InvalidArgument(
+            name.to_owned(),
+            "compounding periods must be at least 1".to_owned(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn check_nper(name: &str, nper: Num) -> Result<()> {
+    if nper == 0. {
+        return Err(Error::InvalidArgument(
+            name.to_owned(),
+            "number of periods can not be zero".to_owned(),
+        ));
+    }
+
+    Ok(())
+}
+
+// Present value of cash flows with the first one at time zero
+fn net_present_value(rate: Num, flows: &[Num]) -> Num {
+    flows
+        .iter()
+        .enumerate()
+        .map(|(i, c)| c / (1. + rate).powi(i as i32))
+        .sum()
+}
+
+fn newton(guess: Num, f: impl Fn(Num) -> Num, df: impl Fn(Num) -> Num) -> Option<Num> {
+    let mut x = guess;
+    for _ in 0..MAX_ITER {
+        let step = f(x) / df(x);
+        if !step.is_finite() {
+            return None;
+        }
+
+        x -= step;
+        if step.abs() < EPSILON * x.abs().max(1.) {
+            return Some(x);
+        }
+    }
+
+    None
+}
+
+fn bisect(f: impl Fn(Num) -> Num, mut lo: Num, mut hi: Num) -> Option<Num> {
+    if f(lo).signum() == f(hi).signum() {
+        return None;
+    }
+
+    for _ in 0..MAX_ITER * 2 {
+        let mid = (lo + hi) / 2.;
+        match f(mid).signum() == f(lo).signum() {
+            true => lo = mid,
+            false => hi = mid,
+        }
+    }
+
+    Some((lo + hi) / 2.)
+}
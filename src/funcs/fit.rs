@@ -0,0 +1,277 @@
+//! Least squares curve fitting.
+//!
+//! Every fit returns `[coefficients, r²]`, with R² measured against the original data. Giving
+//! a name as the last argument also defines the fitted curve as a function of `x`, so
+//! `linfit(xs, ys, f)` makes `f(3)` work.
+
+use super::{eval_list, linear::least_squares, poly::Polynomial, reqire_args_range, Function};
+use crate::calc::{sequence, simplify::tree, solver::Context, Error, Num, Ops, Result, Token};
+
+// Highest degree `polyfit` allows, the normal equations get unstable well before this
+const MAX_DEGREE: Num = 20.;
+
+pub struct LinFit;
+impl Function for LinFit {
+    fn name(&self) -> &'static str {
+        "linfit"
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    // (xs, ys, [name]), fits y = m x + c and gives [[m, c], r²]
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args_range(self.name(), &args, 2, 3)?;
+        let (xs, ys) = data(self.name(), &args, 2, context)?;
+        let poly = polyfit(self.name(), &xs, &ys, 1)?;
+        let model = poly.to_token("x");
+        finish(
+            self.name(),
+            args.get(2),
+            poly.0.into_iter().rev().collect(),
+            model,
+            &xs,
+            &ys,
+            context,
+        )
+    }
+}
+
+pub struct PolyFit;
+impl Function for PolyFit {
+    fn name(&self) -> &'static str {
+        "polyfit"
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    // (xs, ys, degree, [name]), the coefficients start with the highest power
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args_range(self.name(), &args, 3, 4)?;
+        let (xs, ys) = data(self.name(), &args, 1, context)?;
+        let degree = context.evaluate(args[2].to_owned())?;
+        if degree.fract() != 0. || !(0. ..=MAX_DEGREE).contains(&degree) {
+            return Err(Error::InvalidArgument(
+                self.name().to_owned(),
+                format!("the degree must be a whole number up to {}", MAX_DEGREE),
+            ));
+        }
+
+        let poly = polyfit(self.name(), &xs, &ys, degree as usize)?;
+        let model = poly.to_token("x");
+        finish(
+            self.name(),
+            args.get(3),
+            poly.0.into_iter().rev().collect(),
+            model,
+            &xs,
+            &ys,
+            context,
+        )
+    }
+}
+
+pub struct ExpFit;
+impl Function for ExpFit {
+    fn name(&self) -> &'static str {
+        "expfit"
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    // (xs, ys, [name]), fits y = a e^(b x) through ln(y) = ln(a) + b x and gives [[a, b], r²]
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args_range(self.name(), &args, 2, 3)?;
+        let (xs, ys) = data(self.name(), &args, 2, context)?;
+        let ln_ys = logs(self.name(), "y", &ys)?;
+        let line = polyfit(self.name(), &xs, &ln_ys, 1)?;
+        let (a, b) = (line.0[0].exp(), line.0[1]);
+
+        let exp = Token::Func(
+            "exp".to_owned(),
+            vec![vec![tree(
+                Ops::Mul,
+                Token::Number(b),
+                Token::Var("x".to_owned()),
+            )]],
+        );
+        let model = tree(Ops::Mul, Token::Number(a), exp);
+        finish(
+            self.name(),
+            args.get(2),
+            vec![a, b],
+            model,
+            &xs,
+            &ys,
+            context,
+        )
+    }
+}
+
+pub struct PowFit;
+impl Function for PowFit {
+    fn name(&self) -> &'static str {
+        "powfit"
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    // (xs, ys, [name]), fits y = a x^b through ln(y) = ln(a) + b ln(x) and gives [[a, b], r²]
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args_range(self.name(), &args, 2, 3)?;
+        let (xs, ys) = data(self.name(), &args, 2, context)?;
+        let ln_xs = logs(self.name(), "x", &xs)?;
+        let ln_ys = logs(self.name(), "y", &ys)?;
+        let line = polyfit(self.name(), &ln_xs, &ln_ys, 1)?;
+        let (a, b) = (line.0[0].exp(), line.0[1]);
+
+        let power = tree(Ops::Pow, Token::Var("x".to_owned()), Token::Number(b));
+        let model = tree(Ops::Mul, Token::Number(a), power);
+        finish(
+            self.name(),
+            args.get(2),
+            vec![a, b],
+            model,
+            &xs,
+            &ys,
+            context,
+        )
+    }
+}
+
+// Reads the x and y lists, which need to be the same length with at least `min` points
+fn data(
+    name: &str,
+    args: &[Token],
+    min: usize,
+    context: &mut Context,
+) -> Result<(Vec<Num>, Vec<Num>)> {
+    let xs = context.evaluate_value(args[0].to_owned())?;
+    let xs = eval_list(vec![xs], context)?;
+    let ys = context.evaluate_value(args[1].to_owned())?;
+    let ys = eval_list(vec![ys], context)?;
+
+    if xs.len() != ys.len() {
+        return Err(Error::InvalidArgument(
+            name.to_owned(),
+            format!("got {} x values and {} y values", xs.len(), ys.len()),
+        ));
+    }
+    if xs.len() < min {
+        return Err(Error::InvalidArgument(
+            name.to_owned(),
+            format!("needs at least {} points", min),
+        ));
+    }
+
+    Ok((xs, ys))
+}
+
+fn logs(name: &str, what: &str, values: &[Num]) -> Result<Vec<Num>> {
+    if values.iter().any(|x| *x <= 0.) {
+        return Err(Error::InvalidArgument(
+            name.to_owned(),
+            format!("every {} value must be positive", what),
+        ));
+    }
+
+    Ok(values.iter().map(|x| x.ln()).collect())
+}
+
+fn polyfit(name: &str, xs: &[Num], ys: &[Num], degree: usize) -> Result<Polynomial> {
+    if xs.len() <= degree {
+        return Err(Error::InvalidArgument(
+            name.to_owned(),
+            format!("needs at least {} points", degree + 1),
+        ));
+    }
+
+    let rows = xs
+        .iter()
+        .zip(ys)
+        .map(|(x, y)| {
+            let mut row = (0..=degree).map(|i| x.powi(i as i32)).collect::<Vec<_>>();
+            row.push(*y);
+            row
+        })
+        .collect::<Vec<_>>();
+
+    // Too few distinct x values leave the system without a unique solution
+    least_squares(name, &rows, degree + 1)
+        .map(Polynomial)
+        .map_err(|_| {
+            Error::InvalidArgument(
+                name.to_owned(),
+                format!("needs at least {} different x values", degree + 1),
+            )
+        })
+}
+
+// Works out R², defines the function if a name was given and builds the result
+fn finish(
+    name: &str,
+    define: Option<&Token>,
+    coeffs: Vec<Num>,
+    model: Token,
+    xs: &[Num],
+    ys: &[Num],
+    context: &mut Context,
+) -> Result<Token> {
+    let predicted = xs
+        .iter()
+        .map(|x| context.evaluate_with(&model, "x", *x))
+        .collect::<Result<Vec<_>>>()?;
+    let mean = ys.iter().sum::<Num>() / ys.len() as Num;
+    let residual = ys
+        .iter()
+        .zip(&predicted)
+        .map(|(y, p)| (y - p).powi(2))
+        .sum::<Num>();
+    let total = ys.iter().map(|y| (y - mean).powi(2)).sum::<Num>();
+    let r2 = match total {
+        0. => 1.,
+        total => 1. - residual / total,
+    };
+
+    match define {
+        Some(Token::Var(func)) => {
+            let left = Token::Func(func.to_owned(), vec![vec![Token::Var("x".to_owned())]]);
+            sequence::define(&left, &model, context)?;
+        }
+        Some(i) => {
+            return Err(Error::InvalidArgument(
+                name.to_owned(),
+                format!("expected a function name, got `{}`", i),
+            ))
+        }
+        None => {}
+    }
+
+    Ok(Token::List(vec![
+        Token::List(coeffs.into_iter().map(Token::Number).collect()),
+        Token::Number(r2),
+    ]))
+}
@@ -0,0 +1,108 @@
+//! Fractions and simple closed forms for decimal results, like `1 / 3` or `sqrt(2) / 2`.
+
+use std::f64::consts::{E, PI};
+
+use super::{
+    poly::{convergents, rational},
+    reqire_args_range, Function,
+};
+use crate::calc::{simplify::tree, solver::Context, Error, Num, Ops, Result, Token};
+
+// Largest denominator tried for plain fractions and for multiples of constants. Small enough
+// that a match within rounding error is very unlikely to be a coincidence.
+const MAX_DENOMINATOR: i128 = 10_000;
+const MAX_MULTIPLE_DENOMINATOR: i128 = 1_000;
+// Square roots looked for, `sqrt(8)` is `2 sqrt(2)` so only square free numbers are needed
+const MAX_SQRT: i128 = 30;
+// Denominator `frac` approximates with when there is no exact match
+const DEFAULT_APPROX_DENOMINATOR: Num = 1_000.;
+
+pub struct Frac;
+impl Function for Frac {
+    fn name(&self) -> &'static str {
+        "frac"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn is_symbolic(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        let fraction = self.call_value(args, context)?;
+        context.evaluate(fraction)
+    }
+
+    // (x, [max denominator]), exact fractions and closed forms are used when there is one,
+    // otherwise the closest convergent with a small enough denominator
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args_range(self.name(), &args, 1, 2)?;
+        let x = context.evaluate(args[0].to_owned())?;
+        let max_den = match args.get(1) {
+            Some(i) => context.evaluate(i.to_owned())?,
+            None => DEFAULT_APPROX_DENOMINATOR,
+        };
+        if max_den.fract() != 0. || !(1. ..=1e15).contains(&max_den) {
+            return Err(Error::InvalidArgument(
+                self.name().to_owned(),
+                "the largest denominator must be a whole number from 1 to 1e15".to_owned(),
+            ));
+        }
+
+        if let Some(i) = closed_form(x) {
+            return Ok(i);
+        }
+
+        convergents(x)
+            .take_while(|(_, k)| *k <= max_den as i128)
+            .last()
+            .map(|(h, k)| multiple(h, k, None))
+            .ok_or(Error::InvalidArgument(
+                self.name().to_owned(),
+                format!("`{}` is too large for a fraction", x),
+            ))
+    }
+}
+
+/// Finds an exact form of `x` as a fraction or a fractional multiple of `pi`, `e` or a square
+/// root, if one is within rounding error.
+pub fn closed_form(x: Num) -> Option<Token> {
+    if let Some((h, k)) = rational(x, MAX_DENOMINATOR) {
+        return Some(multiple(h, k, None));
+    }
+
+    let constants =
+        [("pi", PI), ("e", E)].map(|(name, value)| (Token::Var(name.to_owned()), value));
+    let roots = (2..=MAX_SQRT).filter(|x| is_square_free(*x)).map(|x| {
+        let root = Token::Func("sqrt".to_owned(), vec![vec![Token::Number(x as Num)]]);
+        (root, (x as Num).sqrt())
+    });
+
+    constants
+        .into_iter()
+        .chain(roots)
+        .find_map(|(token, value)| {
+            rational(x / value, MAX_MULTIPLE_DENOMINATOR).map(|(h, k)| multiple(h, k, Some(token)))
+        })
+}
+
+// `h * base / k`, leaving out ones
+fn multiple(h: i128, k: i128, base: Option<Token>) -> Token {
+    let top = match (h, base) {
+        (h, None) => Token::Number(h as Num),
+        (1, Some(base)) => base,
+        (h, Some(base)) => tree(Ops::Mul, Token::Number(h as Num), base),
+    };
+
+    match k {
+        1 => top,
+        k => tree(Ops::Div, top, Token::Number(k as Num)),
+    }
+}
+
+fn is_square_free(x: i128) -> bool {
+    (2..).take_while(|i| i * i <= x).all(|i| x % (i * i) != 0)
+}
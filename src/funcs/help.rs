@@ -0,0 +1,156 @@
+//! Usage lines for every built-in function, shown by `:help` in the REPL.
+
+/// How to call a built-in function and what it does, like `floor(x): largest whole number at
+/// most x`.
+pub fn help(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "floor" => "floor(x): largest whole number at most x",
+        "ceil" => "ceil(x): smallest whole number at least x",
+        "round" => "round(x): closest whole number, halves round away from zero",
+        "trunc" => "trunc(x): whole part of x, rounding towards zero",
+        "fract" => "fract(x): fractional part of x, x - trunc(x)",
+        "abs" => "abs(x): absolute value",
+        "exp" => "exp(x): e^x",
+        "exp_2" => "exp_2(x): 2^x",
+        "ln" => "ln(x): natural logarithm",
+        "log_2" => "log_2(x): base 2 logarithm",
+        "log_10" => "log_10(x): base 10 logarithm",
+        "cbrt" => "cbrt(x): cube root",
+        "exp_m_1" => "exp_m_1(x): e^x - 1, accurate for small x",
+        "ln_1_p" => "ln_1_p(x): ln(1 + x), accurate for small x",
+        "sqrt" => "sqrt(x): square root",
+        "sin" => "sin(x): sine, with x in the current angle mode",
+        "cos" => "cos(x): cosine, with x in the current angle mode",
+        "tan" => "tan(x): tangent, with x in the current angle mode",
+        "asin" => "asin(x): inverse sine, in the current angle mode",
+        "acos" => "acos(x): inverse cosine, in the current angle mode",
+        "atan" => "atan(x): inverse tangent, in the current angle mode",
+        "sinh" => "sinh(x): hyperbolic sine",
+        "cosh" => "cosh(x): hyperbolic cosine",
+        "tanh" => "tanh(x): hyperbolic tangent",
+        "asinh" => "asinh(x): inverse hyperbolic sine",
+        "acosh" => "acosh(x): inverse hyperbolic cosine",
+        "atanh" => "atanh(x): inverse hyperbolic tangent",
+        "recip" => "recip(x): 1 / x",
+        "to_degrees" => "to_degrees(x): converts radians to degrees",
+        "to_radians" => "to_radians(x): converts degrees to radians",
+        "sig_num" => "sig_num(x): 1 for positive x, -1 for negative x",
+        "deg" => "deg(x): an angle of x degrees in the current angle mode, what `x°` means",
+        "is_infinite" => "is_infinite(x): 1 if x is infinite, otherwise 0",
+        "is_nan" => "is_nan(x): 1 if x is not a number, otherwise 0",
+        "is_finite" => "is_finite(x): 1 if x is neither infinite nor NaN, otherwise 0",
+        "is_subnormal" => "is_subnormal(x): 1 if x is subnormal, otherwise 0",
+        "is_normal" => "is_normal(x): 1 if x is neither zero, infinite, subnormal nor NaN, otherwise 0",
+        "if" => "if(cond, a, b): a if cond is above 0, otherwise b",
+        "not" => "not(x): 1 if x is at most 0, otherwise 0",
+        "and" => "and(a, b): 1 if both a and b are above 0, otherwise 0",
+        "or" => "or(a, b): 1 if a or b is above 0, otherwise 0",
+        "xor" => "xor(a, b): 1 if exactly one of a and b is above 0, otherwise 0",
+        "lt" => "lt(a, b): compares a and b as truth values, where above 0 is true",
+        "le" => "le(a, b): compares a and b as truth values, where above 0 is true",
+        "gt" => "gt(a, b): compares a and b as truth values, where above 0 is true",
+        "ge" => "ge(a, b): compares a and b as truth values, where above 0 is true",
+        "eq" => "eq(a, b): compares a and b as truth values, where above 0 is true",
+        "ne" => "ne(a, b): compares a and b as truth values, where above 0 is true",
+        "exit" => "exit([code]): quits with the exit code",
+        "dbg" => "dbg(x): prints an expression with its value and gives the value",
+        "min" => "min(a, b): smaller of a and b",
+        "max" => "max(a, b): larger of a and b",
+        "log" => "log(x, base): logarithm of x in any base",
+        "hypot" => "hypot(a, b): sqrt(a^2 + b^2)",
+        "atan_2" => "atan_2(y, x): angle of the point (x, y), in the current angle mode",
+        "clamp" => "clamp(x, min, max): x limited to the range from min to max",
+        "lerp" => "lerp(t, a, b): a + (b - a) t",
+        "factorial" => "factorial(n): n!, using the gamma function for fractions",
+        "gcf" => "gcf(a, b): greatest common factor",
+        "lcm" => "lcm(a, b): least common multiple",
+        "dist" => "dist(dice): prints the exact distribution of a dice expression and gives its mean",
+        "gamma" => "gamma(x): the gamma function, (x - 1)! for whole x",
+        "lgamma" => "lgamma(x): ln(abs(gamma(x))), without overflowing",
+        "digamma" => "digamma(x): derivative of lgamma",
+        "beta" => "beta(a, b): the beta function",
+        "erf" => "erf(x): the error function",
+        "erfc" => "erfc(x): 1 - erf(x), accurate for large x",
+        "erfinv" => "erfinv(x): inverse of erf",
+        "gammainc" => "gammainc(a, x): regularized lower incomplete gamma function",
+        "gammaincc" => "gammaincc(a, x): regularized upper incomplete gamma function",
+        "betainc" => "betainc(x, a, b): regularized incomplete beta function",
+        "zeta" => "zeta(s): the Riemann zeta function",
+        "bessel_j0" => "bessel_j0(x): Bessel function of the first kind of order 0",
+        "bessel_j1" => "bessel_j1(x): Bessel function of the first kind of order 1",
+        "normpdf" => "normpdf(x, [mean, sd]): normal density, mean 0 and sd 1 by default",
+        "normcdf" => "normcdf(x, [mean, sd]): normal cumulative probability, mean 0 and sd 1 by default",
+        "norminv" => "norminv(p, [mean, sd]): normal quantile, mean 0 and sd 1 by default",
+        "tpdf" => "tpdf(x, df): Student's t density",
+        "tcdf" => "tcdf(x, df): Student's t cumulative probability",
+        "tinv" => "tinv(p, df): Student's t quantile",
+        "chisqpdf" => "chisqpdf(x, k): chi-squared density",
+        "chisqcdf" => "chisqcdf(x, k): chi-squared cumulative probability",
+        "chisqinv" => "chisqinv(p, k): chi-squared quantile",
+        "exppdf" => "exppdf(x, [rate]): exponential density, rate 1 by default",
+        "expcdf" => "expcdf(x, [rate]): exponential cumulative probability, rate 1 by default",
+        "expinv" => "expinv(p, [rate]): exponential quantile, rate 1 by default",
+        "binompmf" => "binompmf(k, n, p): probability of k successes in n trials",
+        "binomcdf" => "binomcdf(k, n, p): probability of at most k successes in n trials",
+        "binominv" => "binominv(q, n, p): smallest k with binomcdf(k, n, p) at least q",
+        "poisspmf" => "poisspmf(k, rate): Poisson probability of k events",
+        "poisscdf" => "poisscdf(k, rate): Poisson probability of at most k events",
+        "poissinv" => "poissinv(q, rate): smallest k with poisscdf(k, rate) at least q",
+        "unifpdf" => "unifpdf(x, [a, b]): uniform density, from 0 to 1 by default",
+        "unifcdf" => "unifcdf(x, [a, b]): uniform cumulative probability, from 0 to 1 by default",
+        "unifinv" => "unifinv(p, [a, b]): uniform quantile, from 0 to 1 by default",
+        "geompmf" => "geompmf(k, p): probability the first success is on trial k",
+        "geomcdf" => "geomcdf(k, p): probability the first success is by trial k",
+        "geominv" => "geominv(q, p): smallest k with geomcdf(k, p) at least q",
+        "pmt" => "pmt(rate, nper, pv, [fv, type]): payment per period of a loan or annuity",
+        "pv" => "pv(rate, nper, pmt, [fv, type]): present value of a series of payments",
+        "fv" => "fv(rate, nper, pmt, [pv, type]): future value of a series of payments",
+        "nper" => "nper(rate, pmt, pv, [fv, type]): number of periods to pay off a loan",
+        "rate" => "rate(nper, pmt, pv, [fv, type, guess]): interest rate per period",
+        "npv" => "npv(rate, cash flows...): net present value, the first cash flow is after one period",
+        "irr" => "irr(cash flows...): internal rate of return, the first cash flow is at time zero",
+        "compound" => "compound(principal, rate, compounds per period, periods): compound interest",
+        "effect" => "effect(nominal rate, compounds per year): effective annual rate",
+        "nominal" => "nominal(effective rate, compounds per year): nominal annual rate",
+        "amort" => "amort(rate, nper, principal): prints an amortization schedule and gives the total interest",
+        "integrate" => "integrate(expr, var, a, b, [tolerance]): definite integral",
+        "deriv" => "deriv(expr, var, at): numeric derivative at a point",
+        "limit" => "limit(expr, var, at, [side]): limit, from below for a negative side and above for a positive one",
+        "grad" => "grad(expr, [vars], [at]): gradient at a point, exact using dual numbers for most functions",
+        "jacobian" => "jacobian([exprs], [vars], [at]): Jacobian matrix with a row for each expression",
+        "solve" => "solve(expr, var, guess) or solve(expr, var, a, b): a root of an expression or equation",
+        "roots" => "roots(expr, var, a, b, [samples]): every root found between a and b",
+        "sum" => "sum(expr, var, from, to, [tolerance]): sum over a range, which can go to inf",
+        "prod" => "prod(expr, var, from, to, [tolerance]): product over a range, which can go to inf",
+        "seq" => "seq(expr, var, from, to, [step]): list of values over a range",
+        "diff" => "diff(expr, var, [order]): symbolic derivative",
+        "simplify" => "simplify(expr): simplified expression",
+        "taylor" => "taylor(expr, var, a, n): Taylor polynomial of order n around a",
+        "coeffs" => "coeffs(expr, var): polynomial coefficients, lowest power first",
+        "expand" => "expand(expr, var): multiplied out polynomial",
+        "factor" => "factor(expr, var): polynomial factored over the integers",
+        "polydiv" => "polydiv(dividend, divisor, var): [quotient, remainder] of polynomial division",
+        "polyroots" => "polyroots(expr, var): every root of a polynomial, real ones first",
+        "linsolve" => "linsolve([equations], [vars], [assign]): solves linear equations, storing the solution if assign is true. Exact when every coefficient is a fraction, otherwise floating point",
+        "ode" => "ode(dy/dt, t, y, t0, y0, t1, [samples]): integrates a differential equation from t0 to t1",
+        "minimize" => "minimize(expr, x, a, b) or minimize(expr, [vars], [start]): [argmin, min]",
+        "maximize" => "maximize(expr, x, a, b) or maximize(expr, [vars], [start]): [argmax, max]",
+        "linfit" => "linfit(xs, ys, [name]): [[m, c], r²] of y = m x + c, defining name(x) if given",
+        "polyfit" => "polyfit(xs, ys, degree, [name]): [[coefficients], r²], highest power first, defining name(x) if given",
+        "expfit" => "expfit(xs, ys, [name]): [[a, b], r²] of y = a e^(b x), defining name(x) if given",
+        "powfit" => "powfit(xs, ys, [name]): [[a, b], r²] of y = a x^b, defining name(x) if given",
+        "interp" => "interp(xs, ys, x, [error / clamp / extrapolate]): linear interpolation",
+        "spline" => "spline(xs, ys, x, [error / clamp / extrapolate]): natural cubic spline interpolation",
+        "lagrange" => "lagrange(xs, ys, x, [error / clamp / extrapolate]): polynomial interpolation for a few points",
+        "fft" => "fft(list): discrete Fourier transform",
+        "ifft" => "ifft(list): inverse discrete Fourier transform",
+        "convolve" => "convolve(a, b): full convolution of two lists",
+        "correlate" => "correlate(a, b): full cross correlation of two lists",
+        "window" => "window(hann / hamming / blackman / rect, n): window of length n",
+        "magnitude" => "magnitude(z): absolute value of a complex number or of every one in a list",
+        "phase" => "phase(z): angle of a complex number or of every one in a list, in radians",
+        "complex" => "complex(re, im): a complex number",
+        "frac" => "frac(x, [max denominator]): x as a fraction, or a multiple of pi, e or a square root",
+        _ => return None,
+    })
+}
@@ -0,0 +1,255 @@
+//! Interpolation over tabulated data, like `interp(xs, ys, 2.5)`.
+//!
+//! The x values have to be sorted and distinct. The point to look up can also be a list, which
+//! gives a list back. Points outside the data are an error by default, the optional last
+//! argument picks what to do instead: `clamp` uses the closest end of the data and
+//! `extrapolate` continues the curve past it.
+
+use std::cmp::Ordering;
+
+use super::{eval_list, reqire_args_range, var_arg, Function};
+use crate::calc::{solver::Context, Error, Num, Result, Token};
+
+// Interpolating polynomials through more points than this swing wildly between them
+const MAX_LAGRANGE_POINTS: usize = 20;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Method {
+    Linear,
+    Spline,
+    Lagrange,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Extrapolate {
+    Error,
+    Clamp,
+    Extend,
+}
+
+macro_rules! interp_func {
+    ($name:ident, $str:expr, $method:expr) => {
+        pub struct $name;
+
+        impl Function for $name {
+            fn name(&self) -> &'static str {
+                $str
+            }
+
+            fn is_pure(&self) -> bool {
+                true
+            }
+
+            fn takes_names(&self) -> bool {
+                true
+            }
+
+            fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+                self.call_value(args, context)?.as_number()
+            }
+
+            // (xs, ys, x, [error / clamp / extrapolate])
+            fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+                interpolate(self.name(), $method, args, context)
+            }
+        }
+    };
+}
+
+interp_func!(Interp, "interp", Method::Linear);
+interp_func!(Spline, "spline", Method::Spline);
+interp_func!(Lagrange, "lagrange", Method::Lagrange);
+
+struct Table {
+    xs: Vec<Num>,
+    ys: Vec<Num>,
+    // Second derivatives at each point, only used by splines
+    second: Vec<Num>,
+}
+
+impl Table {
+    fn new(name: &str, method: Method, xs: Vec<Num>, ys: Vec<Num>) -> Result<Self> {
+        if xs.len() != ys.len() {
+            return Err(Error::InvalidArgument(
+                name.to_owned(),
+                format!("got {} x values and {} y values", xs.len(), ys.len()),
+            ));
+        }
+        if xs.len() < 2 {
+            return Err(Error::InvalidArgument(
+                name.to_owned(),
+                "needs at least 2 points".to_owned(),
+            ));
+        }
+        if method == Method::Lagrange && xs.len() > MAX_LAGRANGE_POINTS {
+            return Err(Error::InvalidArgument(
+                name.to_owned(),
+                format!(
+                    "only works for up to {} points, try `spline` instead",
+                    MAX_LAGRANGE_POINTS
+                ),
+            ));
+        }
+        if xs
+            .windows(2)
+            .any(|x| x[0].partial_cmp(&x[1]) != Some(Ordering::Less))
+        {
+            return Err(Error::InvalidArgument(
+                name.to_owned(),
+                "the x values must be sorted from smallest to largest, without repeats".to_owned(),
+            ));
+        }
+
+        let mut table = Self {
+            second: vec![0.; xs.len()],
+            xs,
+            ys,
+        };
+        if method == Method::Spline {
+            table.natural_spline();
+        }
+        Ok(table)
+    }
+
+    fn min(&self) -> Num {
+        self.xs[0]
+    }
+
+    fn max(&self) -> Num {
+        self.xs[self.xs.len() - 1]
+    }
+
+    // Index of the first point of the segment containing `x`, using the end segments past the
+    // ends of the data
+    fn segment(&self, x: Num) -> usize {
+        self.xs
+            .partition_point(|i| *i <= x)
+            .clamp(1, self.xs.len() - 1)
+            - 1
+    }
+
+    fn at(&self, method: Method, x: Num) -> Num {
+        match method {
+            Method::Linear => self.linear(x),
+            Method::Spline => self.spline(x),
+            Method::Lagrange => self.lagrange(x),
+        }
+    }
+
+    fn linear(&self, x: Num) -> Num {
+        let i = self.segment(x);
+        let (x0, x1, y0, y1) = (self.xs[i], self.xs[i + 1], self.ys[i], self.ys[i + 1]);
+        y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+    }
+
+    // Natural splines have no curvature at the ends, which leaves a tridiagonal system for the
+    // second derivatives at the inner points
+    fn natural_spline(&mut self) {
+        let (xs, ys) = (&self.xs, &self.ys);
+        let n = xs.len();
+        let mut diag = vec![0.; n];
+        let mut rhs = vec![0.; n];
+
+        for i in 1..n - 1 {
+            let (h0, h1) = (xs[i] - xs[i - 1], xs[i + 1] - xs[i]);
+            diag[i] = 2. * (h0 + h1);
+            rhs[i] = 6. * ((ys[i + 1] - ys[i]) / h1 - (ys[i] - ys[i - 1]) / h0);
+            if i > 1 {
+                let w = h0 / diag[i - 1];
+                diag[i] -= w * h0;
+                rhs[i] -= w * rhs[i - 1];
+            }
+        }
+
+        for i in (1..n - 1).rev() {
+            let h1 = xs[i + 1] - xs[i];
+            self.second[i] = (rhs[i] - h1 * self.second[i + 1]) / diag[i];
+        }
+    }
+
+    fn spline(&self, x: Num) -> Num {
+        let i = self.segment(x);
+        let (x0, x1, y0, y1) = (self.xs[i], self.xs[i + 1], self.ys[i], self.ys[i + 1]);
+        let (m0, m1) = (self.second[i], self.second[i + 1]);
+        let h = x1 - x0;
+        let (a, b) = (x1 - x, x - x0);
+
+        (m0 * a.powi(3) + m1 * b.powi(3)) / (6. * h)
+            + (y0 / h - m0 * h / 6.) * a
+            + (y1 / h - m1 * h / 6.) * b
+    }
+
+    fn lagrange(&self, x: Num) -> Num {
+        let mut out = 0.;
+        for (i, (xi, yi)) in self.xs.iter().zip(&self.ys).enumerate() {
+            let basis = self
+                .xs
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, xj)| (x - xj) / (xi - xj))
+                .product::<Num>();
+            out += yi * basis;
+        }
+        out
+    }
+}
+
+fn interpolate(
+    name: &str,
+    method: Method,
+    args: Vec<Token>,
+    context: &mut Context,
+) -> Result<Token> {
+    reqire_args_range(name, &args, 3, 4)?;
+    let extrapolate = match args.get(3) {
+        None => Extrapolate::Error,
+        Some(i) => match var_arg(name, i)?.as_str() {
+            "error" => Extrapolate::Error,
+            "clamp" => Extrapolate::Clamp,
+            "extrapolate" => Extrapolate::Extend,
+            i => {
+                return Err(Error::InvalidArgument(
+                    name.to_owned(),
+                    format!(
+                        "unknown extrapolation `{}`, expected `error`, `clamp` or `extrapolate`",
+                        i
+                    ),
+                ))
+            }
+        },
+    };
+
+    let xs = context.evaluate_value(args[0].to_owned())?;
+    let xs = eval_list(vec![xs], context)?;
+    let ys = context.evaluate_value(args[1].to_owned())?;
+    let ys = eval_list(vec![ys], context)?;
+    let table = Table::new(name, method, xs, ys)?;
+
+    let at = |x: Num| -> Result<Num> {
+        let x = match extrapolate {
+            _ if (table.min()..=table.max()).contains(&x) => x,
+            Extrapolate::Error => {
+                return Err(Error::OutOfRange(
+                    name.to_owned(),
+                    x,
+                    table.min(),
+                    table.max(),
+                ))
+            }
+            Extrapolate::Clamp => x.clamp(table.min(), table.max()),
+            Extrapolate::Extend => x,
+        };
+        Ok(table.at(method, x))
+    };
+
+    match context.evaluate_value(args[2].to_owned())? {
+        Token::List(items) => Ok(Token::List(
+            eval_list(items, context)?
+                .into_iter()
+                .map(|x| at(x).map(Token::Number))
+                .collect::<Result<_>>()?,
+        )),
+        i => at(context.evaluate(i)?).map(Token::Number),
+    }
+}
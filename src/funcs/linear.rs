@@ -0,0 +1,359 @@
+//! Systems of linear equations.
+//!
+//! Systems where every coefficient is a fraction, like `0.5` or `1 / 3`, are solved exactly and
+//! only rounded at the end. Anything else, or fractions too large for `i128`, falls back to
+//! floating point.
+
+use super::{
+    poly::{gcd, rational},
+    reqire_args_range,
+    roots::equation_to_expr,
+    symbolic::{depends_on, expression},
+    var_arg, Function,
+};
+use crate::calc::{solver::Context, Error, Num, Ops, Result, Token};
+
+// Pivots smaller than this, relative to the largest coefficient, count as zero
+const EPSILON: Num = 1e-12;
+// Largest denominator a coefficient can have to be treated as an exact fraction
+const MAX_DENOMINATOR: i128 = 1_000_000;
+
+pub struct LinSolve;
+impl Function for LinSolve {
+    fn name(&self) -> &'static str {
+        "linsolve"
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    // ([equations], [vars], [assign]), the solution is stored in the variables if `assign` is true
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args_range(self.name(), &args, 2, 3)?;
+        let equations = match &args[0] {
+            Token::List(items) => items.to_owned(),
+            i => vec![i.to_owned()],
+        };
+        let vars = match &args[1] {
+            Token::List(items) => items.iter().map(|x| var_arg(self.name(), x)).collect(),
+            i => var_arg(self.name(), i).map(|x| vec![x]),
+        }?;
+        let assign = match args.get(2) {
+            Some(i) => context.evaluate(i.to_owned())? > 0.,
+            None => false,
+        };
+
+        // Each row holds the coefficients followed by the right hand side
+        let mut matrix = Vec::new();
+        for i in equations.iter() {
+            let (mut row, constant) = linear(self.name(), &equation_to_expr(i), &vars, context)?;
+            row.push(-constant);
+            matrix.push(row);
+        }
+
+        let solution = match gauss_exact(self.name(), &matrix, vars.len()) {
+            Some(solution) => solution?,
+            None => gauss(self.name(), matrix, vars.len())?,
+        };
+        if assign {
+            for (var, value) in vars.iter().zip(solution.iter()) {
+                context.set_var(var, Token::Number(*value));
+            }
+        }
+
+        Ok(Token::List(
+            solution.into_iter().map(Token::Number).collect(),
+        ))
+    }
+}
+
+/// Splits an expression that is linear in `vars` into its coefficients and constant term.
+fn linear(
+    name: &str,
+    expr: &Token,
+    vars: &[String],
+    context: &mut Context,
+) -> Result<(Vec<Num>, Num)> {
+    let constant = |x| (vec![0.; vars.len()], x);
+    if !vars.iter().any(|x| depends_on(expr, x, context)) {
+        return Ok(constant(context.evaluate(expr.to_owned())?));
+    }
+
+    let not_linear = || {
+        Error::InvalidArgument(
+            name.to_owned(),
+            format!("`{}` is not linear in {}", expr, vars.join(", ")),
+        )
+    };
+
+    Ok(match expr {
+        Token::Var(i) => match vars.iter().position(|x| *x == i.to_lowercase()) {
+            Some(index) => {
+                let mut out = constant(0.);
+                out.0[index] = 1.;
+                out
+            }
+            None => {
+                let inner = expression(expr, context)?;
+                if inner == *expr {
+                    return Err(not_linear());
+                }
+                linear(name, &inner, vars, context)?
+            }
+        },
+        Token::Func(..) => {
+            let inner = expression(expr, context)?;
+            if inner == *expr {
+                return Err(not_linear());
+            }
+            linear(name, &inner, vars, context)?
+        }
+        Token::Tree(op @ (Ops::Add | Ops::Sub), left, right) => {
+            let sign = if matches!(op, Ops::Sub) { -1. } else { 1. };
+            let (mut coeffs, constant) = linear(name, left, vars, context)?;
+            let (right, right_constant) = linear(name, right, vars, context)?;
+            coeffs
+                .iter_mut()
+                .zip(right)
+                .for_each(|(a, b)| *a += sign * b);
+            (coeffs, constant + sign * right_constant)
+        }
+        Token::Tree(op @ (Ops::Mul | Ops::Div), left, right) => {
+            // One side has to be a constant, it scales the other one
+            let (scale, expr) = match (op, linear(name, left, vars, context)?) {
+                (Ops::Mul, (coeffs, scale)) if coeffs.iter().all(|x| *x == 0.) => {
+                    (scale, linear(name, right, vars, context)?)
+                }
+                (_, expr) => match linear(name, right, vars, context)? {
+                    (coeffs, scale) if coeffs.iter().all(|x| *x == 0.) => match op {
+                        Ops::Mul => (scale, expr),
+                        _ => (scale.recip(), expr),
+                    },
+                    _ => return Err(not_linear()),
+                },
+            };
+            (expr.0.iter().map(|x| x * scale).collect(), expr.1 * scale)
+        }
+        _ => return Err(not_linear()),
+    })
+}
+
+/// Least squares solution of an overdetermined system, with each row holding the coefficients
+/// followed by the right hand side. Solves the normal equations `AᵀA x = Aᵀb`.
+pub fn least_squares(name: &str, rows: &[Vec<Num>], vars: usize) -> Result<Vec<Num>> {
+    let matrix = (0..vars)
+        .map(|i| {
+            (0..=vars)
+                .map(|j| rows.iter().map(|x| x[i] * x[j]).sum())
+                .collect()
+        })
+        .collect();
+    gauss(name, matrix, vars)
+}
+
+/// Gaussian elimination with partial pivoting on an augmented matrix, returns the unique solution.
+fn gauss(name: &str, mut matrix: Vec<Vec<Num>>, vars: usize) -> Result<Vec<Num>> {
+    let scale = matrix
+        .iter()
+        .flat_map(|x| x[..vars].iter())
+        .fold(0., |a: Num, x| a.max(x.abs()));
+    let is_zero = |x: Num| x.abs() <= EPSILON * scale;
+
+    let mut rank = 0;
+    for col in 0..vars {
+        let pivot = (rank..matrix.len())
+            .max_by(|a, b| matrix[*a][col].abs().total_cmp(&matrix[*b][col].abs()))
+            .filter(|x| !is_zero(matrix[*x][col]));
+        let Some(pivot) = pivot else {
+            continue;
+        };
+
+        matrix.swap(rank, pivot);
+        let pivot_row = matrix[rank].to_owned();
+        for (i, row) in matrix.iter_mut().enumerate() {
+            if i == rank {
+                continue;
+            }
+
+            let factor = row[col] / pivot_row[col];
+            for (a, b) in row.iter_mut().zip(pivot_row.iter()).skip(col) {
+                *a -= factor * b;
+            }
+        }
+        rank += 1;
+    }
+
+    // Rows left over after elimination read 0 = rhs
+    let rhs_scale = matrix.iter().fold(scale, |a, x| a.max(x[vars].abs()));
+    if matrix[rank..]
+        .iter()
+        .any(|x| x[vars].abs() > EPSILON * rhs_scale)
+    {
+        return Err(Error::NoSolution(name.to_owned()));
+    }
+    if rank < vars {
+        return Err(Error::InfiniteSolutions(name.to_owned()));
+    }
+
+    // Every column has a pivot, so the first rows are diagonal
+    Ok((0..vars)
+        .map(|i| matrix[i][vars] / matrix[i][i] + 0.)
+        .collect())
+}
+
+/// Fraction in lowest terms with a positive denominator. Every operation gives None on overflow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Ratio(i128, i128);
+
+impl Ratio {
+    fn new(num: i128, den: i128) -> Option<Self> {
+        if den == 0 {
+            return None;
+        }
+        let divisor = gcd(num, den) * den.signum();
+        Some(Self(num / divisor, den / divisor))
+    }
+
+    fn from_num(x: Num) -> Option<Self> {
+        let (num, den) = rational(x, MAX_DENOMINATOR)?;
+        Self::new(num, den)
+    }
+
+    fn to_num(self) -> Num {
+        self.0 as Num / self.1 as Num
+    }
+
+    fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    fn sub(self, other: Self) -> Option<Self> {
+        Self::new(
+            self.0
+                .checked_mul(other.1)?
+                .checked_sub(other.0.checked_mul(self.1)?)?,
+            self.1.checked_mul(other.1)?,
+        )
+    }
+
+    fn mul(self, other: Self) -> Option<Self> {
+        Self::new(self.0.checked_mul(other.0)?, self.1.checked_mul(other.1)?)
+    }
+
+    fn div(self, other: Self) -> Option<Self> {
+        Self::new(self.0.checked_mul(other.1)?, self.1.checked_mul(other.0)?)
+    }
+}
+
+/// Like `gauss`, but with exact fractions so no tolerance is needed to tell whether a system is
+/// singular. None when a coefficient is not a fraction or the arithmetic overflows.
+fn gauss_exact(name: &str, matrix: &[Vec<Num>], vars: usize) -> Option<Result<Vec<Num>>> {
+    let mut matrix = matrix
+        .iter()
+        .map(|x| x.iter().map(|x| Ratio::from_num(*x)).collect())
+        .collect::<Option<Vec<Vec<_>>>>()?;
+
+    let mut rank = 0;
+    for col in 0..vars {
+        let Some(pivot) = (rank..matrix.len()).find(|x| !matrix[*x][col].is_zero()) else {
+            continue;
+        };
+
+        matrix.swap(rank, pivot);
+        let pivot_row = matrix[rank].to_owned();
+        for (i, row) in matrix.iter_mut().enumerate() {
+            if i == rank || row[col].is_zero() {
+                continue;
+            }
+
+            let factor = row[col].div(pivot_row[col])?;
+            for (a, b) in row.iter_mut().zip(pivot_row.iter()).skip(col) {
+                *a = a.sub(factor.mul(*b)?)?;
+            }
+        }
+        rank += 1;
+    }
+
+    if matrix[rank..].iter().any(|x| !x[vars].is_zero()) {
+        return Some(Err(Error::NoSolution(name.to_owned())));
+    }
+    if rank < vars {
+        return Some(Err(Error::InfiniteSolutions(name.to_owned())));
+    }
+
+    (0..vars)
+        .map(|i| matrix[i][vars].div(matrix[i][i]).map(Ratio::to_num))
+        .collect::<Option<Vec<_>>>()
+        .map(Ok)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_close(actual: &[Num], expected: &[Num]) {
+        assert!(
+            actual
+                .iter()
+                .zip(expected)
+                .all(|(a, b)| (a - b).abs() <= 1e-12),
+            "got {actual:?}, expected {expected:?}"
+        );
+    }
+
+    #[test]
+    fn test_exact() {
+        // 2x + 4y = 1, 3x - y = 2
+        let matrix = [vec![2., 4., 1.], vec![3., -1., 2.]];
+        let solution = gauss_exact("", &matrix, 2).unwrap().unwrap();
+        assert_eq!(solution, [9. / 14., -1. / 14.]);
+        assert_close(&gauss("", matrix.to_vec(), 2).unwrap(), &solution);
+
+        // Fractions in the coefficients are read exactly as well
+        let matrix = [vec![0.5, 1. / 3., 1.], vec![1., -1., 0.]];
+        assert_eq!(gauss_exact("", &matrix, 2).unwrap().unwrap(), [1.2, 1.2]);
+    }
+
+    #[test]
+    fn test_singular() {
+        let dependent = [vec![1., 1., 1.], vec![2., 2., 2.]];
+        let inconsistent = [vec![1., 1., 1.], vec![2., 2., 3.]];
+        assert!(matches!(
+            gauss_exact("", &dependent, 2),
+            Some(Err(Error::InfiniteSolutions(_)))
+        ));
+        assert!(matches!(
+            gauss_exact("", &inconsistent, 2),
+            Some(Err(Error::NoSolution(_)))
+        ));
+        assert!(matches!(
+            gauss("", dependent.to_vec(), 2),
+            Err(Error::InfiniteSolutions(_))
+        ));
+        assert!(matches!(
+            gauss("", inconsistent.to_vec(), 2),
+            Err(Error::NoSolution(_))
+        ));
+    }
+
+    #[test]
+    fn test_overdetermined() {
+        // x = 1, y = 2, x + y = 3 agree, x + y = 4 does not
+        let mut matrix = vec![vec![1., 0., 1.], vec![0., 1., 2.], vec![1., 1., 3.]];
+        assert_eq!(gauss_exact("", &matrix, 2).unwrap().unwrap(), [1., 2.]);
+        matrix[2][2] = 4.;
+        assert!(matches!(
+            gauss_exact("", &matrix, 2),
+            Some(Err(Error::NoSolution(_)))
+        ));
+
+        // Best fit of y = m x + c through (0, 1), (1, 3), (2, 4)
+        let rows = [vec![0., 1., 1.], vec![1., 1., 3.], vec![2., 1., 4.]];
+        assert_close(&least_squares("", &rows, 2).unwrap(), &[1.5, 7. / 6.]);
+    }
+}
@@ -12,6 +12,10 @@ macro_rules! bool_func {
                 stringify!($name)
             }
 
+            fn is_pure(&self) -> bool {
+                true
+            }
+
             fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
                 reqire_args(self.name(), &args, 1)?;
                 Ok(context.evaluate(args[0].to_owned())?.$func() as u8 as Num)
@@ -29,6 +33,10 @@ macro_rules! logic_func {
                 stringify!($name)
             }
 
+            fn is_pure(&self) -> bool {
+                true
+            }
+
             fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
                 reqire_args(self.name(), &args, 2)?;
 
@@ -61,6 +69,10 @@ impl Function for If {
         "if"
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
         reqire_args(self.name(), &args, 3)?;
         let cond = context.evaluate(args[0].to_owned())?;
@@ -77,6 +89,10 @@ impl Function for Not {
         "not"
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
         reqire_args(self.name(), &args, 1)?;
         Ok((context.evaluate(args[0].to_owned())? <= 0 as Num) as u8 as Num)
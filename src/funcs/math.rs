@@ -1,20 +1,37 @@
-use super::{reqire_args, Function};
-use crate::calc::{solver::Context, Num, Result, Token};
+use super::{reqire_args, special, Function};
+use crate::calc::{
+    angle::AngleMode, dual::Dual, number::Number, solver::Context, Num, Result, Token,
+};
 
 macro_rules! multi_func {
     ($name:ident, $func:ident) => {
         pub struct $name;
 
+        impl $name {
+            fn apply<N: Number>(a: N, b: N) -> N {
+                a.$func(b)
+            }
+        }
+
         impl Function for $name {
             fn name(&self) -> &'static str {
                 stringify!($name)
             }
 
+            fn is_pure(&self) -> bool {
+                true
+            }
+
             fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
                 reqire_args(self.name(), &args, 2)?;
                 let a = context.evaluate(args[0].to_owned())?;
                 let b = context.evaluate(args[1].to_owned())?;
-                Ok(a.$func(b))
+                Ok(Self::apply(a, b))
+            }
+
+            fn call_dual(&self, args: Vec<Dual>, _context: &mut Context) -> Result<Dual> {
+                reqire_args(self.name(), &args, 2)?;
+                Ok(Self::apply(args[0], args[1]))
             }
         }
     };
@@ -24,35 +41,94 @@ multi_func!(Min, min);
 multi_func!(Max, max);
 multi_func!(Log, log);
 multi_func!(Hypot, hypot);
-multi_func!(Atan2, atan2);
+
+// Gives its angle in the current angle mode
+pub struct Atan2;
+impl Atan2 {
+    fn apply<N: Number>(y: N, x: N, angle: AngleMode) -> N {
+        angle.convert_radians(y.atan2(x))
+    }
+}
+
+impl Function for Atan2 {
+    fn name(&self) -> &'static str {
+        "Atan2"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        reqire_args(self.name(), &args, 2)?;
+        let y = context.evaluate(args[0].to_owned())?;
+        let x = context.evaluate(args[1].to_owned())?;
+        Ok(Self::apply(y, x, context.angle()))
+    }
+
+    fn call_dual(&self, args: Vec<Dual>, context: &mut Context) -> Result<Dual> {
+        reqire_args(self.name(), &args, 2)?;
+        Ok(Self::apply(args[0], args[1], context.angle()))
+    }
+}
 
 pub struct Clamp;
+impl Clamp {
+    fn apply<N: Number>(val: N, min: N, max: N) -> N {
+        val.clamp(min, max)
+    }
+}
+
 impl Function for Clamp {
     fn name(&self) -> &'static str {
         "clamp"
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
         reqire_args(self.name(), &args, 3)?;
         let val = context.evaluate(args[0].to_owned())?;
         let min = context.evaluate(args[1].to_owned())?;
         let max = context.evaluate(args[2].to_owned())?;
-        Ok(val.clamp(min, max))
+        Ok(Self::apply(val, min, max))
+    }
+
+    fn call_dual(&self, args: Vec<Dual>, _context: &mut Context) -> Result<Dual> {
+        reqire_args(self.name(), &args, 3)?;
+        Ok(Self::apply(args[0], args[1], args[2]))
     }
 }
 
 pub struct Lerp;
+impl Lerp {
+    fn apply<N: Number>(t: N, a: N, b: N) -> N {
+        a + (b - a) * t
+    }
+}
+
 impl Function for Lerp {
     fn name(&self) -> &'static str {
         "lerp"
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
         reqire_args(self.name(), &args, 3)?;
         let t = context.evaluate(args[0].to_owned())?;
         let a = context.evaluate(args[1].to_owned())?;
         let b = context.evaluate(args[2].to_owned())?;
-        Ok(a + (b - a) * t)
+        Ok(Self::apply(t, a, b))
+    }
+
+    fn call_dual(&self, args: Vec<Dual>, _context: &mut Context) -> Result<Dual> {
+        reqire_args(self.name(), &args, 3)?;
+        Ok(Self::apply(args[0], args[1], args[2]))
     }
 }
 
@@ -62,9 +138,18 @@ impl Function for Factorial {
         "factorial"
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
         reqire_args(self.name(), &args, 1)?;
-        let mut n = context.evaluate(args[0].to_owned())? as u64;
+        let n = context.evaluate(args[0].to_owned())?;
+        if n.fract() != 0. || !(0. ..=20.).contains(&n) {
+            return Ok(special::gamma(n + 1.));
+        }
+
+        let mut n = n as u64;
         let mut result = 1;
         while n > 1 {
             result *= n;
@@ -72,39 +157,80 @@ impl Function for Factorial {
         }
         Ok(result as Num)
     }
+
+    // d/dn n! = Γ(n + 1) ψ(n + 1)
+    fn call_dual(&self, args: Vec<Dual>, context: &mut Context) -> Result<Dual> {
+        reqire_args(self.name(), &args, 1)?;
+        let n = args[0];
+        let value = self.call(vec![Token::Number(n.re)], context)?;
+        Ok(n.chain(value, value * special::digamma(n.re + 1.)))
+    }
 }
 
 pub struct Gcf;
+impl Gcf {
+    // Arguments are truncated to integers, so the result does not change with small steps
+    fn apply<N: Number>(a: N, b: N) -> N {
+        let mut a = a.value() as u64;
+        let mut b = b.value() as u64;
+
+        while b != 0 {
+            let temp = b;
+            b = a % b;
+            a = temp;
+        }
+
+        N::constant(a as Num)
+    }
+}
+
 impl Function for Gcf {
     fn name(&self) -> &'static str {
         "gcf"
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
         reqire_args(self.name(), &args, 2)?;
-        let mut a = context.evaluate(args[0].to_owned())? as u64;
-        let mut b = context.evaluate(args[1].to_owned())? as u64;
-
-        while b != 0 {
-            let temp = b;
-            b = a % b;
-            a = temp;
-        }
+        let a = context.evaluate(args[0].to_owned())?;
+        let b = context.evaluate(args[1].to_owned())?;
+        Ok(Self::apply(a, b))
+    }
 
-        Ok(a as Num)
+    fn call_dual(&self, args: Vec<Dual>, _context: &mut Context) -> Result<Dual> {
+        reqire_args(self.name(), &args, 2)?;
+        Ok(Self::apply(args[0], args[1]))
     }
 }
 
 pub struct Lcm;
+impl Lcm {
+    fn apply<N: Number>(a: N, b: N) -> N {
+        a * b / Gcf::apply(a, b)
+    }
+}
+
 impl Function for Lcm {
     fn name(&self) -> &'static str {
         "lcm"
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
         reqire_args(self.name(), &args, 2)?;
         let a = context.evaluate(args[0].to_owned())?;
         let b = context.evaluate(args[1].to_owned())?;
-        Ok((a * b / Gcf.call(args, context)?) as Num)
+        Ok(Self::apply(a, b))
+    }
+
+    fn call_dual(&self, args: Vec<Dual>, _context: &mut Context) -> Result<Dual> {
+        reqire_args(self.name(), &args, 2)?;
+        Ok(Self::apply(args[0], args[1]))
     }
 }
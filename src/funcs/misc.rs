@@ -10,11 +10,11 @@ impl Function for Exit {
     }
 
     fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
-        let code = match args.get(0) {
+        let code = match args.first() {
             Some(arg) => context.evaluate(arg.to_owned())? as i32,
             None => 0,
         };
-        process::exit(code as i32);
+        process::exit(code);
     }
 }
 
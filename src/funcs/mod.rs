@@ -1,9 +1,30 @@
-use crate::calc::{solver::Context, Error, Num, Result, Token};
+use crate::calc::{dual::Dual, solver::Context, Error, Num, Result, Token};
 
 mod basic;
+mod calculus;
+mod dice;
+mod finance;
+mod fit;
+mod frac;
+mod help;
+mod interp;
+mod linear;
 mod logic;
 mod math;
 mod misc;
+mod ode;
+mod optimize;
+mod poly;
+mod roots;
+mod series;
+mod signal;
+mod special;
+mod stats;
+mod symbolic;
+
+pub use frac::closed_form;
+pub use help::help;
+pub use symbolic::depends_on;
 
 // == Misc ==
 // cmp
@@ -41,6 +62,7 @@ pub const FUNCTIONS: &[&dyn Function] = &[
     &basic::ToDegrees,
     &basic::ToRadians,
     &basic::SigNum,
+    &basic::Deg,
     &logic::IsInfinite,
     &logic::IsNan,
     &logic::IsFinite,
@@ -69,14 +91,148 @@ pub const FUNCTIONS: &[&dyn Function] = &[
     &math::Factorial,
     &math::Gcf,
     &math::Lcm,
+    &dice::Dist,
+    &special::Gamma,
+    &special::LGamma,
+    &special::Digamma,
+    &special::Beta,
+    &special::Erf,
+    &special::Erfc,
+    &special::ErfInv,
+    &special::GammaInc,
+    &special::GammaIncC,
+    &special::BetaInc,
+    &special::Zeta,
+    &special::BesselJ0,
+    &special::BesselJ1,
+    &stats::NormPdf,
+    &stats::NormCdf,
+    &stats::NormInv,
+    &stats::TPdf,
+    &stats::TCdf,
+    &stats::TInv,
+    &stats::ChiSqPdf,
+    &stats::ChiSqCdf,
+    &stats::ChiSqInv,
+    &stats::ExpPdf,
+    &stats::ExpCdf,
+    &stats::ExpInv,
+    &stats::BinomPmf,
+    &stats::BinomCdf,
+    &stats::BinomInv,
+    &stats::PoissPmf,
+    &stats::PoissCdf,
+    &stats::PoissInv,
+    &stats::UnifPdf,
+    &stats::UnifCdf,
+    &stats::UnifInv,
+    &stats::GeomPmf,
+    &stats::GeomCdf,
+    &stats::GeomInv,
+    &finance::Pmt,
+    &finance::Pv,
+    &finance::Fv,
+    &finance::Nper,
+    &finance::Rate,
+    &finance::Npv,
+    &finance::Irr,
+    &finance::Compound,
+    &finance::Effect,
+    &finance::Nominal,
+    &finance::Amort,
+    &calculus::Integrate,
+    &calculus::Deriv,
+    &roots::Solve,
+    &roots::Roots,
+    &series::Sum,
+    &series::Prod,
+    &symbolic::Diff,
+    &symbolic::Simplify,
+    &symbolic::Taylor,
+    &calculus::Limit,
+    &poly::Coeffs,
+    &poly::Expand,
+    &poly::Factor,
+    &poly::PolyDiv,
+    &poly::PolyRoots,
+    &linear::LinSolve,
+    &ode::Ode,
+    &optimize::Minimize,
+    &optimize::Maximize,
+    &calculus::Grad,
+    &calculus::Jacobian,
+    &series::Seq,
+    &fit::LinFit,
+    &fit::PolyFit,
+    &fit::ExpFit,
+    &fit::PowFit,
+    &interp::Interp,
+    &interp::Spline,
+    &interp::Lagrange,
+    &signal::Fft,
+    &signal::Ifft,
+    &signal::Convolve,
+    &signal::Correlate,
+    &signal::Window,
+    &signal::Magnitude,
+    &signal::Phase,
+    &signal::MakeComplex,
+    &frac::Frac,
 ];
 
 pub trait Function {
     fn name(&self) -> &'static str;
+
+    /// Pure functions always give the same result for the same arguments and have no side
+    /// effects, so `simplify` can fold calls with constant arguments.
+    fn is_pure(&self) -> bool {
+        false
+    }
+
+    /// Symbolic functions like `diff` return expressions, which other symbolic functions work on
+    /// directly instead of treating the call as an opaque function.
+    fn is_symbolic(&self) -> bool {
+        false
+    }
+
+    /// Functions that take plain names after their first argument, like the variable in
+    /// `sum(k^2, k, 1, 3)` or the modes of `interp`, so highlighting does not mark them unknown.
+    fn takes_names(&self) -> bool {
+        false
+    }
+
     fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num>;
+
+    /// Functions that can return something other than a number, like a list, override this.
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        self.call(args, context).map(Token::Number)
+    }
+
+    /// Calls the function with dual numbers, which gives its exact derivative for `grad`.
+    /// Functions that are generic over `Number` implement this with the same code as `call`,
+    /// anything else gets a numeric derivative for each argument that carries one.
+    fn call_dual(&self, args: Vec<Dual>, context: &mut Context) -> Result<Dual> {
+        let values = args.iter().map(|x| x.re).collect::<Vec<_>>();
+        let call = |values: Vec<Num>, context: &mut Context| {
+            self.call(values.into_iter().map(Token::Number).collect(), context)
+        };
+
+        let value = call(values.to_owned(), context)?;
+        let mut eps = 0.;
+        for (i, arg) in args.iter().enumerate().filter(|x| x.1.eps != 0.) {
+            let (slope, _) = calculus::derivative(arg.re, |x| {
+                let mut values = values.to_owned();
+                values[i] = x;
+                call(values, context)
+            })?;
+            eps += slope * arg.eps;
+        }
+
+        Ok(Dual::new(value, eps))
+    }
 }
 
-pub fn reqire_args(name: &str, args: &[Token], count: usize) -> Result<()> {
+pub fn reqire_args<T>(name: &str, args: &[T], count: usize) -> Result<()> {
     if args.len() != count {
         return Err(Error::InvalidArgumentCount(
             name.to_owned(),
@@ -87,3 +243,41 @@ pub fn reqire_args(name: &str, args: &[Token], count: usize) -> Result<()> {
 
     Ok(())
 }
+
+pub fn reqire_args_range(name: &str, args: &[Token], min: usize, max: usize) -> Result<()> {
+    if args.len() < min || args.len() > max {
+        return Err(Error::InvalidArgumentCount(
+            name.to_owned(),
+            args.len(),
+            args.len().clamp(min, max),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Evaluates every argument, expanding lists into their items, including lists held by
+/// variables or returned by functions.
+pub fn eval_list(args: Vec<Token>, context: &mut Context) -> Result<Vec<Num>> {
+    let mut out = Vec::new();
+    for i in args {
+        match context.evaluate_value(i)? {
+            Token::List(items) => out.extend(eval_list(items, context)?),
+            Token::Number(x) => out.push(x),
+            i => out.push(context.evaluate(i)?),
+        }
+    }
+
+    Ok(out)
+}
+
+/// Gets the name out of an argument that should be a bare variable, like the `x` in `deriv(x^2, x, 1)`.
+pub fn var_arg(name: &str, arg: &Token) -> Result<String> {
+    match arg {
+        Token::Var(i) => Ok(i.to_lowercase()),
+        i => Err(Error::InvalidArgument(
+            name.to_owned(),
+            format!("expected a variable name, got `{}`", i),
+        )),
+    }
+}
@@ -0,0 +1,234 @@
+//! Ordinary differential equations.
+
+use super::{reqire_args_range, var_arg, Function};
+use crate::calc::{solver::Context, Error, Num, Result, Token};
+
+const TOLERANCE: Num = 1e-10;
+const MAX_STEPS: usize = 100_000;
+// Samples can be used for tables, so there should not be too many
+const MAX_SAMPLES: Num = 10_000.;
+
+// Dormand-Prince coefficients, the last row of A is also the fifth order solution
+const C: [Num; 7] = [0., 1. / 5., 3. / 10., 4. / 5., 8. / 9., 1., 1.];
+const A: [[Num; 6]; 7] = [
+    [0., 0., 0., 0., 0., 0.],
+    [1. / 5., 0., 0., 0., 0., 0.],
+    [3. / 40., 9. / 40., 0., 0., 0., 0.],
+    [44. / 45., -56. / 15., 32. / 9., 0., 0., 0.],
+    [
+        19372. / 6561.,
+        -25360. / 2187.,
+        64448. / 6561.,
+        -212. / 729.,
+        0.,
+        0.,
+    ],
+    [
+        9017. / 3168.,
+        -355. / 33.,
+        46732. / 5247.,
+        49. / 176.,
+        -5103. / 18656.,
+        0.,
+    ],
+    [
+        35. / 384.,
+        0.,
+        500. / 1113.,
+        125. / 192.,
+        -2187. / 6784.,
+        11. / 84.,
+    ],
+];
+// Difference between the fifth and fourth order weights, used to estimate the error
+const E: [Num; 7] = [
+    35. / 384. - 5179. / 57600.,
+    0.,
+    500. / 1113. - 7571. / 16695.,
+    125. / 192. - 393. / 640.,
+    -2187. / 6784. + 92097. / 339200.,
+    11. / 84. - 187. / 2100.,
+    -1. / 40.,
+];
+
+pub struct Ode;
+impl Function for Ode {
+    fn name(&self) -> &'static str {
+        "ode"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    // (dy/dt, t, y, t0, y0, t1, [samples]), systems use lists for `dy/dt`, `y` and `y0`.
+    // Returns y(t1), or a list of [t, y...] rows at `samples` evenly spaced steps.
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args_range(self.name(), &args, 6, 7)?;
+        let system = matches!(args[2], Token::List(_));
+        let exprs = list_arg(&args[0]);
+        let t = var_arg(self.name(), &args[1])?;
+        let vars = list_arg(&args[2])
+            .iter()
+            .map(|x| var_arg(self.name(), x))
+            .collect::<Result<Vec<_>>>()?;
+        let t0 = context.evaluate(args[3].to_owned())?;
+        let y0 = list_arg(&args[4])
+            .into_iter()
+            .map(|x| context.evaluate(x))
+            .collect::<Result<Vec<_>>>()?;
+        let t1 = context.evaluate(args[5].to_owned())?;
+        let samples = match args.get(6) {
+            Some(i) => Some(context.evaluate(i.to_owned())?),
+            None => None,
+        };
+
+        if exprs.len() != vars.len() || y0.len() != vars.len() {
+            return Err(Error::InvalidArgument(
+                self.name().to_owned(),
+                "expected a derivative and a starting value for every variable".to_owned(),
+            ));
+        }
+        if !t0.is_finite() || !t1.is_finite() {
+            return Err(Error::InvalidArgument(
+                self.name().to_owned(),
+                "expected a finite interval".to_owned(),
+            ));
+        }
+        if samples.is_some_and(|x| !(1. ..=MAX_SAMPLES).contains(&x)) {
+            return Err(Error::InvalidArgument(
+                self.name().to_owned(),
+                format!("expected between 1 and {} samples", MAX_SAMPLES),
+            ));
+        }
+
+        let mut f = |t_value: Num, y: &[Num]| -> Result<Vec<Num>> {
+            let bindings = vars
+                .iter()
+                .map(|x| x.as_str())
+                .zip(y.iter().copied())
+                .chain([(t.as_str(), t_value)]);
+            context.with_locals(bindings, |ctx| {
+                exprs.iter().map(|x| ctx.evaluate(x.to_owned())).collect()
+            })
+        };
+        let value = |y: Vec<Num>| match system {
+            true => Token::List(y.into_iter().map(Token::Number).collect()),
+            false => Token::Number(y[0]),
+        };
+
+        let mut solver = Solver::new(y0, t1 - t0);
+        let Some(samples) = samples else {
+            solver.step_to(self.name(), t0, t1, &mut f)?;
+            return Ok(value(solver.y));
+        };
+
+        // Integrating from sample to sample lands on every sample time exactly
+        let samples = samples as usize;
+        let mut rows = Vec::new();
+        let mut prev = t0;
+        for i in 0..=samples {
+            let t = t0 + (t1 - t0) * i as Num / samples as Num;
+            solver.step_to(self.name(), prev, t, &mut f)?;
+            prev = t;
+
+            let mut row = vec![Token::Number(t)];
+            row.extend(solver.y.iter().map(|x| Token::Number(*x)));
+            rows.push(Token::List(row));
+        }
+
+        Ok(Token::List(rows))
+    }
+}
+
+fn list_arg(arg: &Token) -> Vec<Token> {
+    match arg {
+        Token::List(items) => items.to_owned(),
+        i => vec![i.to_owned()],
+    }
+}
+
+/// Adaptive Runge-Kutta integrator using the Dormand-Prince method, which gives a fifth order
+/// solution along with a fourth order one to estimate the error.
+struct Solver {
+    y: Vec<Num>,
+    // Step size to try next, carried over between calls to `step_to`
+    step: Num,
+}
+
+impl Solver {
+    fn new(y: Vec<Num>, span: Num) -> Self {
+        Self {
+            y,
+            step: span.abs() / 100.,
+        }
+    }
+
+    // Advances `y` from `t` to `end`
+    fn step_to(
+        &mut self,
+        name: &str,
+        mut t: Num,
+        end: Num,
+        f: &mut impl FnMut(Num, &[Num]) -> Result<Vec<Num>>,
+    ) -> Result<()> {
+        let direction = (end - t).signum();
+        let mut steps = 0;
+        while (end - t) * direction > 0. {
+            steps += 1;
+            let h = self.step.min((end - t).abs()) * direction;
+            if steps > MAX_STEPS || t + h == t {
+                return Err(Error::NoConvergence(name.to_owned()));
+            }
+
+            let mut k: Vec<Vec<Num>> = Vec::with_capacity(7);
+            for (c, a) in C.iter().zip(A.iter()) {
+                let y = (0..self.y.len())
+                    .map(|i| self.y[i] + h * k.iter().zip(a).map(|(k, a)| a * k[i]).sum::<Num>())
+                    .collect::<Vec<_>>();
+                k.push(f(t + c * h, &y)?);
+            }
+
+            // The last stage is evaluated at the new point
+            let next = (0..self.y.len())
+                .map(|i| self.y[i] + h * k.iter().zip(A[6]).map(|(k, a)| a * k[i]).sum::<Num>())
+                .collect::<Vec<_>>();
+            let error = (0..self.y.len())
+                .map(|i| {
+                    let estimate = h * k.iter().zip(E).map(|(k, e)| e * k[i]).sum::<Num>();
+                    let scale = TOLERANCE * (1. + self.y[i].abs().max(next[i].abs()));
+                    (estimate / scale).abs()
+                })
+                .fold(0., Num::max);
+            if error.is_nan() || next.iter().any(|x| x.is_nan()) {
+                return Err(Error::NoConvergence(name.to_owned()));
+            }
+
+            // A step shortened to land on `end` says nothing about the next step size
+            let shortened = h.abs() < self.step;
+            if error <= 1. {
+                t += h;
+                self.y = next;
+                if shortened {
+                    continue;
+                }
+            }
+
+            let factor = match error {
+                0. => 5.,
+                e => (0.9 * e.powf(-0.2)).clamp(0.2, 5.),
+            };
+            self.step = h.abs() * factor;
+        }
+
+        Ok(())
+    }
+}
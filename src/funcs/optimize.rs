@@ -0,0 +1,272 @@
+//! Finding the minimum or maximum of a function.
+
+use super::{reqire_args, var_arg, Function};
+use crate::calc::{solver::Context, Error, Num, Result, Token};
+
+const EPSILON: Num = 1e-10;
+const MAX_ITER: usize = 10_000;
+// (3 - sqrt(5)) / 2, the golden section of an interval
+const GOLDEN: Num = 0.381_966_011_250_105_1;
+
+pub struct Minimize;
+impl Function for Minimize {
+    fn name(&self) -> &'static str {
+        "minimize"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    // (expr, x, a, b) or (expr, [x, y...], [x0, y0...]), returns [argmin, min]
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        optimize(self.name(), args, 1., context)
+    }
+}
+
+pub struct Maximize;
+impl Function for Maximize {
+    fn name(&self) -> &'static str {
+        "maximize"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    // (expr, x, a, b) or (expr, [x, y...], [x0, y0...]), returns [argmax, max]
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        optimize(self.name(), args, -1., context)
+    }
+}
+
+// Minimizes `sign * expr`, so maximizing uses a sign of -1
+fn optimize(name: &str, args: Vec<Token>, sign: Num, context: &mut Context) -> Result<Token> {
+    if let Some(Token::List(vars)) = args.get(1) {
+        reqire_args(name, &args, 3)?;
+        let vars = vars
+            .iter()
+            .map(|x| var_arg(name, x))
+            .collect::<Result<Vec<_>>>()?;
+        let start = match &args[2] {
+            Token::List(items) if items.len() == vars.len() => items
+                .iter()
+                .map(|x| context.evaluate(x.to_owned()))
+                .collect::<Result<Vec<_>>>()?,
+            _ => {
+                return Err(Error::InvalidArgument(
+                    name.to_owned(),
+                    "expected a starting point with a value for every variable".to_owned(),
+                ))
+            }
+        };
+
+        let mut f = |x: &[Num]| {
+            let bindings = vars.iter().map(|x| x.as_str()).zip(x.iter().copied());
+            context.with_locals(bindings, |ctx| ctx.evaluate(args[0].to_owned()))
+        };
+        let (point, value) = nelder_mead(start, &mut |x| Ok(sign * f(x)?))?
+            .ok_or_else(|| Error::NoConvergence(name.to_owned()))?;
+
+        return Ok(Token::List(vec![
+            Token::List(point.into_iter().map(Token::Number).collect()),
+            Token::Number(sign * value),
+        ]));
+    }
+
+    reqire_args(name, &args, 4)?;
+    let var = var_arg(name, &args[1])?;
+    let a = context.evaluate(args[2].to_owned())?;
+    let b = context.evaluate(args[3].to_owned())?;
+    if !(a.is_finite() && b.is_finite() && a < b) {
+        return Err(Error::InvalidArgument(
+            name.to_owned(),
+            "expected a finite interval with a < b".to_owned(),
+        ));
+    }
+
+    let mut f = |x| Ok(sign * context.evaluate_with(&args[0], &var, x)?);
+    let (x, value) = brent(a, b, &mut f)?;
+    Ok(Token::List(vec![
+        Token::Number(x),
+        Token::Number(sign * value),
+    ]))
+}
+
+/// Brent's method for a minimum on [a, b], which combines golden section search with parabolic
+/// interpolation. Finds a local minimum, unless one of the ends is lower.
+pub fn brent(mut a: Num, mut b: Num, f: &mut impl FnMut(Num) -> Result<Num>) -> Result<(Num, Num)> {
+    let ends = [(a, f(a)?), (b, f(b)?)];
+
+    // x is the best point so far, w the second best and v the previous value of w
+    let mut x = a + GOLDEN * (b - a);
+    let mut fx = f(x)?;
+    let (mut w, mut fw, mut v, mut fv) = (x, fx, x, fx);
+    // Step taken in the last two iterations
+    let (mut d, mut e): (Num, Num) = (0., 0.);
+
+    for _ in 0..MAX_ITER {
+        let middle = (a + b) / 2.;
+        // Minima are flat, so x can only be found to about the square root of the precision
+        let tol = Num::EPSILON.sqrt() * x.abs() + EPSILON;
+        if (x - middle).abs() <= 2. * tol - (b - a) / 2. {
+            break;
+        }
+
+        // Try a parabola through x, w and v, unless it steps too far or out of [a, b]
+        let mut golden = true;
+        if e.abs() > tol {
+            let r = (x - w) * (fx - fv);
+            let mut q = (x - v) * (fx - fw);
+            let mut p = (x - v) * q - (x - w) * r;
+            q = 2. * (q - r);
+            if q > 0. {
+                p = -p;
+            }
+            q = q.abs();
+
+            if p.abs() < (q * e / 2.).abs() && p > q * (a - x) && p < q * (b - x) {
+                e = d;
+                d = p / q;
+                golden = false;
+
+                // Do not evaluate too close to the ends
+                let u = x + d;
+                if u - a < 2. * tol || b - u < 2. * tol {
+                    d = tol.copysign(middle - x);
+                }
+            }
+        }
+        if golden {
+            e = if x < middle { b - x } else { a - x };
+            d = GOLDEN * e;
+        }
+
+        let u = match d.abs() >= tol {
+            true => x + d,
+            false => x + tol.copysign(d),
+        };
+        let fu = f(u)?;
+
+        if fu <= fx {
+            if u < x {
+                b = x;
+            } else {
+                a = x;
+            }
+            (v, fv, w, fw, x, fx) = (w, fw, x, fx, u, fu);
+        } else {
+            if u < x {
+                a = u;
+            } else {
+                b = u;
+            }
+            if fu <= fw || w == x {
+                (v, fv, w, fw) = (w, fw, u, fu);
+            } else if fu <= fv || v == x || v == w {
+                (v, fv) = (u, fu);
+            }
+        }
+    }
+
+    Ok(ends
+        .into_iter()
+        .filter(|x| !x.1.is_nan())
+        .fold((x, fx), |best, i| if i.1 < best.1 { i } else { best }))
+}
+
+/// Nelder-Mead simplex method for a minimum near `start`.
+/// Returns None if it does not converge.
+pub fn nelder_mead(
+    start: Vec<Num>,
+    f: &mut impl FnMut(&[Num]) -> Result<Num>,
+) -> Result<Option<(Vec<Num>, Num)>> {
+    let n = start.len();
+    let mut simplex = vec![(start.to_owned(), f(&start)?)];
+    for i in 0..n {
+        let mut point = start.to_owned();
+        point[i] += 0.1 * point[i].abs().max(1.);
+        let value = f(&point)?;
+        simplex.push((point, value));
+    }
+
+    // Moves `from` away from the centroid by `factor`
+    let towards = |centroid: &[Num], from: &[Num], factor: Num| {
+        centroid
+            .iter()
+            .zip(from)
+            .map(|(c, x)| c + factor * (x - c))
+            .collect::<Vec<_>>()
+    };
+
+    for _ in 0..MAX_ITER {
+        simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let best = &simplex[0];
+        let size = simplex
+            .iter()
+            .flat_map(|x| x.0.iter().zip(&best.0).map(|(a, b)| (a - b).abs()))
+            .fold(0., Num::max);
+        let scale = best.0.iter().fold(1., |a: Num, x| a.max(x.abs()));
+        if size <= Num::EPSILON.sqrt() * scale {
+            return Ok(Some(simplex.swap_remove(0)));
+        }
+
+        let centroid = (0..n)
+            .map(|i| simplex[..n].iter().map(|x| x.0[i]).sum::<Num>() / n as Num)
+            .collect::<Vec<_>>();
+
+        let reflected = towards(&centroid, &simplex[n].0, -1.);
+        let fr = f(&reflected)?;
+        if fr < simplex[0].1 {
+            let expanded = towards(&centroid, &simplex[n].0, -2.);
+            let fe = f(&expanded)?;
+            simplex[n] = if fe < fr {
+                (expanded, fe)
+            } else {
+                (reflected, fr)
+            };
+            continue;
+        }
+        if fr < simplex[n - 1].1 {
+            simplex[n] = (reflected, fr);
+            continue;
+        }
+
+        // Contract towards the better of the worst point and its reflection
+        let (outside, from) = match fr < simplex[n].1 {
+            true => (true, reflected),
+            false => (false, simplex[n].0.to_owned()),
+        };
+        let contracted = towards(&centroid, &from, 0.5);
+        let fc = f(&contracted)?;
+        if fc < if outside { fr } else { simplex[n].1 } {
+            simplex[n] = (contracted, fc);
+            continue;
+        }
+
+        // Shrink everything towards the best point
+        let best = simplex[0].0.to_owned();
+        for i in simplex.iter_mut().skip(1) {
+            i.0 = towards(&best, &i.0, 0.5);
+            i.1 = f(&i.0)?;
+        }
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,864 @@
+//! Polynomials in one variable.
+//!
+//! Coefficient lists start with the highest power, so `coeffs(x^2 - 1, x)` is `[1, 0, -1]`.
+//! Factoring works on exact integer coefficients, so it only handles polynomials whose
+//! coefficients are (close to) fractions with small denominators.
+
+use std::f64::consts::TAU;
+
+use super::{
+    reqire_args,
+    symbolic::{depends_on, expression},
+    var_arg, Function,
+};
+use crate::calc::{
+    complex::Complex,
+    simplify::{sum_of, tree, with_coeff},
+    solver::Context,
+    Error, Num, Ops, Result, Token,
+};
+
+const EPSILON: Num = 1e-12;
+const MAX_DEGREE: usize = 1000;
+const MAX_ITER: usize = 500;
+// Largest denominator a coefficient can have to be treated as a fraction
+const MAX_DENOMINATOR: i128 = 1_000_000;
+// Largest number whose divisors are tried as roots or factor values
+const MAX_DIVISORS_OF: i128 = 1_000_000_000_000;
+// Candidate factors tried for each degree while factoring
+const MAX_COMBINATIONS: usize = 100_000;
+
+pub struct Coeffs;
+impl Function for Coeffs {
+    fn name(&self) -> &'static str {
+        "coeffs"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    // (expr, var)
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args(self.name(), &args, 2)?;
+        let var = var_arg(self.name(), &args[1])?;
+        let poly = Polynomial::parse(self.name(), &args[0], &var, context)?;
+        Ok(Token::List(
+            poly.0.into_iter().rev().map(Token::Number).collect(),
+        ))
+    }
+}
+
+pub struct Expand;
+impl Function for Expand {
+    fn name(&self) -> &'static str {
+        "expand"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn is_symbolic(&self) -> bool {
+        true
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        let expr = self.call_value(args, context)?;
+        context.evaluate(expr)
+    }
+
+    // (expr, var)
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args(self.name(), &args, 2)?;
+        let var = var_arg(self.name(), &args[1])?;
+        Ok(Polynomial::parse(self.name(), &args[0], &var, context)?.to_token(&var))
+    }
+}
+
+pub struct Factor;
+impl Function for Factor {
+    fn name(&self) -> &'static str {
+        "factor"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn is_symbolic(&self) -> bool {
+        true
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        let expr = self.call_value(args, context)?;
+        context.evaluate(expr)
+    }
+
+    // (expr, var)
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args(self.name(), &args, 2)?;
+        let var = var_arg(self.name(), &args[1])?;
+        let poly = Polynomial::parse(self.name(), &args[0], &var, context)?;
+        if poly.is_zero() {
+            return Ok(Token::Number(0.));
+        }
+
+        let (content, factors) = factor(&poly).ok_or_else(|| {
+            Error::InvalidArgument(
+                self.name().to_owned(),
+                "the coefficients need to be fractions".to_owned(),
+            )
+        })?;
+
+        let product = factors
+            .into_iter()
+            .map(|(f, power)| {
+                let f = Polynomial::from_integer(&f).to_token(&var);
+                match power {
+                    1 => f,
+                    n => tree(Ops::Pow, f, Token::Number(n as Num)),
+                }
+            })
+            .reduce(|a, b| tree(Ops::Mul, a, b));
+
+        Ok(match product {
+            Some(i) => with_coeff(i, content),
+            None => Token::Number(content),
+        })
+    }
+}
+
+pub struct PolyDiv;
+impl Function for PolyDiv {
+    fn name(&self) -> &'static str {
+        "polydiv"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn is_symbolic(&self) -> bool {
+        true
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    // (dividend, divisor, var), returns [quotient, remainder]
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args(self.name(), &args, 3)?;
+        let var = var_arg(self.name(), &args[2])?;
+        let a = Polynomial::parse(self.name(), &args[0], &var, context)?;
+        let b = Polynomial::parse(self.name(), &args[1], &var, context)?;
+        if b.is_zero() {
+            return Err(Error::InvalidArgument(
+                self.name().to_owned(),
+                "can not divide by zero".to_owned(),
+            ));
+        }
+
+        let (quotient, remainder) = a.div_rem(&b);
+        Ok(Token::List(vec![
+            quotient.to_token(&var),
+            remainder.to_token(&var),
+        ]))
+    }
+}
+
+pub struct PolyRoots;
+impl Function for PolyRoots {
+    fn name(&self) -> &'static str {
+        "polyroots"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    // (expr, var), real roots come first in ascending order, followed by the complex ones
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args(self.name(), &args, 2)?;
+        let var = var_arg(self.name(), &args[1])?;
+        let poly = Polynomial::parse(self.name(), &args[0], &var, context)?;
+        if poly.is_zero() {
+            return Err(Error::InvalidArgument(
+                self.name().to_owned(),
+                "every number is a root of zero".to_owned(),
+            ));
+        }
+
+        let mut roots = roots(&poly);
+        roots.sort_by(|a, b| {
+            (a.im != 0.)
+                .cmp(&(b.im != 0.))
+                .then(a.re.total_cmp(&b.re))
+                .then(a.im.total_cmp(&b.im))
+        });
+
+        Ok(Token::List(
+            roots
+                .into_iter()
+                .map(|x| match x.im {
+                    0. => Token::Number(x.re),
+                    _ => Token::Complex(x),
+                })
+                .collect(),
+        ))
+    }
+}
+
+/// Coefficients of a polynomial, lowest power first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polynomial(pub Vec<Num>);
+
+impl Polynomial {
+    /// Reads a polynomial in `var` out of an expression. Parts that do not depend on `var` are
+    /// evaluated, so other variables need to have a value.
+    pub fn parse(name: &str, expr: &Token, var: &str, context: &mut Context) -> Result<Self> {
+        if !depends_on(expr, var, context) {
+            return Ok(Self(vec![context.evaluate(expr.to_owned())?]));
+        }
+
+        let not_polynomial = || {
+            Error::InvalidArgument(
+                name.to_owned(),
+                format!("`{}` is not a polynomial in `{}`", expr, var),
+            )
+        };
+
+        Ok(match expr {
+            Token::Var(i) if i.to_lowercase() == var => Self(vec![0., 1.]),
+            Token::Var(_) | Token::Func(..) => {
+                let inner = expression(expr, context)?;
+                if inner == *expr {
+                    return Err(not_polynomial());
+                }
+                Self::parse(name, &inner, var, context)?
+            }
+            Token::Tree(op, left, right) => {
+                let left = Self::parse(name, left, var, context)?;
+                match op {
+                    Ops::Pow => {
+                        if depends_on(right, var, context) {
+                            return Err(not_polynomial());
+                        }
+
+                        let power = context.evaluate(*right.to_owned())?;
+                        if power < 0. || power.fract() != 0. {
+                            return Err(not_polynomial());
+                        }
+                        if left.degree() * power as usize > MAX_DEGREE {
+                            return Err(Error::InvalidArgument(
+                                name.to_owned(),
+                                format!("the degree can be at most {}", MAX_DEGREE),
+                            ));
+                        }
+
+                        (0..power as usize).fold(Self(vec![1.]), |acc, _| acc.mul(&left))
+                    }
+                    op => {
+                        let right = Self::parse(name, right, var, context)?;
+                        match op {
+                            Ops::Add => left.add(&right),
+                            Ops::Sub => left.add(&right.scale(-1.)),
+                            Ops::Mul => left.mul(&right),
+                            Ops::Div => match left.div_rem(&right) {
+                                (quotient, remainder) if remainder.is_zero() => quotient,
+                                _ => return Err(not_polynomial()),
+                            },
+                            _ => return Err(not_polynomial()),
+                        }
+                    }
+                }
+            }
+            _ => return Err(not_polynomial()),
+        }
+        .trim())
+    }
+
+    fn from_integer(coeffs: &[i128]) -> Self {
+        Self(coeffs.iter().map(|x| *x as Num).collect())
+    }
+
+    pub fn degree(&self) -> usize {
+        self.0.len().saturating_sub(1)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.iter().all(|x| *x == 0.)
+    }
+
+    fn eval_complex(&self, z: Complex) -> Complex {
+        self.0
+            .iter()
+            .rev()
+            .fold(Complex::from(0.), |acc, c| acc * z + Complex::from(*c))
+    }
+
+    fn derivative(&self) -> Self {
+        Self(
+            self.0
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(i, c)| i as Num * c)
+                .collect(),
+        )
+    }
+
+    fn scale(&self, factor: Num) -> Self {
+        Self(self.0.iter().map(|x| x * factor).collect())
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        let mut out = vec![0.; self.0.len().max(other.0.len())];
+        for (i, c) in self.0.iter().enumerate() {
+            out[i] += c;
+        }
+        for (i, c) in other.0.iter().enumerate() {
+            out[i] += c;
+        }
+
+        Self(out).trim()
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        let mut out = vec![0.; self.0.len() + other.0.len() - 1];
+        for (i, a) in self.0.iter().enumerate() {
+            for (j, b) in other.0.iter().enumerate() {
+                out[i + j] += a * b;
+            }
+        }
+
+        Self(out).trim()
+    }
+
+    /// Long division, returns the quotient and the remainder.
+    /// Remainder coefficients that are only rounding error are dropped.
+    pub fn div_rem(&self, other: &Self) -> (Self, Self) {
+        let other = other.to_owned().trim();
+        let lead = other.0[other.degree()];
+        let mut remainder = self.0.to_owned();
+        if self.degree() < other.degree() {
+            return (Self(vec![0.]), self.to_owned());
+        }
+
+        let mut quotient = vec![0.; self.degree() - other.degree() + 1];
+        for i in (0..quotient.len()).rev() {
+            let q = remainder[i + other.degree()] / lead;
+            quotient[i] = q;
+            for (j, c) in other.0.iter().enumerate() {
+                remainder[i + j] -= q * c;
+            }
+        }
+
+        let scale = self.0.iter().fold(0., |a: Num, x| a.max(x.abs()));
+        remainder.truncate(other.degree().max(1));
+        for i in remainder.iter_mut() {
+            if i.abs() <= EPSILON * scale {
+                *i = 0.;
+            }
+        }
+
+        (Self(quotient).trim(), Self(remainder).trim())
+    }
+
+    // Removes leading zero coefficients, keeping at least one
+    fn trim(mut self) -> Self {
+        while self.0.len() > 1 && self.0.last() == Some(&0.) {
+            self.0.pop();
+        }
+        if self.0.is_empty() {
+            self.0.push(0.);
+        }
+
+        self
+    }
+
+    /// Builds an expression with the highest power first.
+    pub fn to_token(&self, var: &str) -> Token {
+        sum_of(self.0.iter().enumerate().rev().map(|(i, c)| {
+            let power = match i {
+                0 => Token::Number(1.),
+                1 => Token::Var(var.to_owned()),
+                i => tree(
+                    Ops::Pow,
+                    Token::Var(var.to_owned()),
+                    Token::Number(i as Num),
+                ),
+            };
+            (power, *c)
+        }))
+    }
+}
+
+/// Closest fraction to `x` with a denominator of at most `max_den`, using continued fractions.
+/// Returns None if no fraction is within rounding error of `x`.
+pub fn rational(x: Num, max_den: i128) -> Option<(i128, i128)> {
+    convergents(x)
+        .take_while(|(_, k)| *k <= max_den)
+        .find(|(h, k)| (*h as Num / *k as Num - x).abs() <= EPSILON * x.abs().max(1.))
+}
+
+/// Continued fraction convergents `h / k` of `x`, each closer than the last. Stops once one is
+/// exact or the next would not fit.
+pub fn convergents(x: Num) -> impl Iterator<Item = (i128, i128)> {
+    // Starting from 0 / 1 and 1 / 0
+    let mut state =
+        (x.is_finite() && x.abs() <= 1e15).then_some((0_i128, 1_i128, 1_i128, 0_i128, x));
+    std::iter::from_fn(move || {
+        let (h0, h1, k0, k1, rest) = state.take()?;
+        let a = rest.floor();
+        let h = (a as i128).checked_mul(h1)?.checked_add(h0)?;
+        let k = (a as i128).checked_mul(k1)?.checked_add(k0)?;
+        if rest != a {
+            state = Some((h1, h, k1, k, 1. / (rest - a)));
+        }
+        Some((h, k))
+    })
+}
+
+// Roots of every factor, so repeated roots are found as accurately as single ones
+fn roots(poly: &Polynomial) -> Vec<Complex> {
+    let factors = match factor(poly) {
+        Some((_, factors)) => factors
+            .into_iter()
+            .map(|(f, power)| (Polynomial::from_integer(&f), power))
+            .collect(),
+        None => vec![(poly.to_owned(), 1)],
+    };
+
+    let mut out = Vec::new();
+    for (f, power) in factors {
+        let roots = match f.degree() {
+            1 => vec![Complex::from(-f.0[0] / f.0[1])],
+            _ => aberth(&f),
+        };
+        for _ in 0..power {
+            out.extend(roots.iter().map(|x| Complex::new(x.re + 0., x.im)));
+        }
+    }
+
+    out
+}
+
+// Aberth-Ehrlich method, finds all roots at once
+fn aberth(poly: &Polynomial) -> Vec<Complex> {
+    let n = poly.degree();
+    if n == 0 {
+        return Vec::new();
+    }
+
+    // Start on a circle inside of Fujiwara's bound on the size of the roots
+    let lead = poly.0[n];
+    let radius = (1..=n)
+        .map(|k| (poly.0[n - k] / lead).abs().powf(1. / k as Num))
+        .fold(0., Num::max)
+        .max(EPSILON);
+    let derivative = poly.derivative();
+    let mut z = (0..n)
+        .map(|k| Complex::from_polar(radius, TAU * k as Num / n as Num + 0.4))
+        .collect::<Vec<_>>();
+
+    for _ in 0..MAX_ITER {
+        let mut done = true;
+        for k in 0..n {
+            let ratio = poly.eval_complex(z[k]) / derivative.eval_complex(z[k]);
+            let repel = (0..n)
+                .filter(|j| *j != k)
+                .fold(Complex::from(0.), |acc, j| {
+                    acc + Complex::from(1.) / (z[k] - z[j])
+                });
+            let offset = ratio / (Complex::from(1.) - ratio * repel);
+            if !offset.re.is_finite() || !offset.im.is_finite() {
+                continue;
+            }
+
+            z[k] = z[k] - offset;
+            done &= offset.abs() <= EPSILON * z[k].abs().max(1.);
+        }
+
+        if done {
+            break;
+        }
+    }
+
+    // Snap nearly real or imaginary roots onto the axes and make the others exact conjugate pairs
+    for i in z.iter_mut() {
+        if i.im.abs() <= EPSILON.sqrt() * i.abs().max(1.) {
+            i.im = 0.;
+        }
+        if i.re.abs() <= EPSILON * i.abs() {
+            i.re = 0.;
+        }
+    }
+    let upper = z.iter().filter(|x| x.im > 0.).copied().collect::<Vec<_>>();
+    if upper.len() * 2 == z.iter().filter(|x| x.im != 0.).count() {
+        z.retain(|x| x.im == 0.);
+        z.extend(upper.iter().flat_map(|x| [*x, x.conj()]));
+    }
+
+    z
+}
+
+// == Exact factoring ==
+//
+// Polynomials with integer coefficients, lowest power first. Anything that would overflow is
+// treated as not factorable.
+
+// Irreducible factors with their powers
+type Factors = Vec<(Vec<i128>, usize)>;
+
+// Returns the content and the factors, with every factor having integer coefficients and a
+// positive leading coefficient
+fn factor(poly: &Polynomial) -> Option<(Num, Factors)> {
+    let (mut f, content) = to_integer(poly)?;
+    let mut factors = Vec::new();
+    while f[0] == 0 && f.len() > 1 {
+        f.remove(0);
+        factors.push(vec![0, 1]);
+    }
+
+    // Linear factors from the rational root theorem, then higher degrees with Kronecker's method
+    'outer: while f.len() > 2 {
+        for q in divisors(f[f.len() - 1])? {
+            for p in divisors(f[0])?.into_iter().flat_map(|x| [x, -x]) {
+                if gcd(p, q) != 1 || eval_integer(&f, p, q) != Some(0) {
+                    continue;
+                }
+
+                let linear = vec![-p, q];
+                f = div_exact(&f, &linear)?;
+                factors.push(linear);
+                continue 'outer;
+            }
+        }
+        break;
+    }
+
+    while let Some(g) = find_factor(&f) {
+        f = div_exact(&f, &g)?;
+        factors.push(g);
+    }
+    if f.len() > 1 {
+        factors.push(f);
+    }
+
+    factors.sort_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)));
+    let mut out: Factors = Vec::new();
+    for i in factors {
+        match out.last_mut() {
+            Some(last) if last.0 == i => last.1 += 1,
+            _ => out.push((i, 1)),
+        }
+    }
+
+    Some((content, out))
+}
+
+// Scales the coefficients to coprime integers with a positive leading coefficient,
+// returns them with the factor that was taken out
+fn to_integer(poly: &Polynomial) -> Option<(Vec<i128>, Num)> {
+    let fractions = poly
+        .0
+        .iter()
+        .map(|x| rational(*x, MAX_DENOMINATOR))
+        .collect::<Option<Vec<_>>>()?;
+    let den = fractions
+        .iter()
+        .try_fold(1_i128, |acc, x| acc.checked_mul(x.1 / gcd(acc, x.1)))?;
+    let mut out = fractions
+        .iter()
+        .map(|x| x.0.checked_mul(den / x.1))
+        .collect::<Option<Vec<_>>>()?;
+
+    let sign = out.last()?.signum();
+    let content = out.iter().fold(0, |acc, x| gcd(acc, *x)) * sign;
+    if content == 0 {
+        return None;
+    }
+    out.iter_mut().for_each(|x| *x /= content);
+
+    Some((out, content as Num / den as Num))
+}
+
+// f(p / q) * q^n, which is zero exactly when p / q is a root
+fn eval_integer(f: &[i128], p: i128, q: i128) -> Option<i128> {
+    // Horner's method from the highest power, the power of q grows as the power of p shrinks
+    let mut acc: i128 = 0;
+    let mut q_power: i128 = 1;
+    for c in f.iter().rev() {
+        acc = acc.checked_mul(p)?.checked_add(c.checked_mul(q_power)?)?;
+        q_power = q_power.checked_mul(q)?;
+    }
+
+    Some(acc)
+}
+
+fn div_exact(f: &[i128], g: &[i128]) -> Option<Vec<i128>> {
+    if g.len() > f.len() {
+        return None;
+    }
+
+    let lead = *g.last()?;
+    let mut rest = f.to_vec();
+    let mut out = vec![0; f.len() - g.len() + 1];
+    for i in (0..out.len()).rev() {
+        let top = rest[i + g.len() - 1];
+        if top % lead != 0 {
+            return None;
+        }
+
+        out[i] = top / lead;
+        for (j, c) in g.iter().enumerate() {
+            rest[i + j] = rest[i + j].checked_sub(out[i].checked_mul(*c)?)?;
+        }
+    }
+
+    rest.iter().all(|x| *x == 0).then_some(out)
+}
+
+// Kronecker's method: a factor of degree d is fixed by its values at d + 1 points, which have
+// to divide the values of `f` at those points
+fn find_factor(f: &[i128]) -> Option<Vec<i128>> {
+    let n = f.len() - 1;
+    for d in 2..=n / 2 {
+        let xs = (0..=d as i128)
+            .map(|i| if i % 2 == 1 { (i + 1) / 2 } else { -i / 2 })
+            .collect::<Vec<_>>();
+        let choices = xs
+            .iter()
+            .enumerate()
+            .map(|(i, x)| {
+                let value = eval_integer(f, *x, 1)?;
+                let divisors = divisors(value)?;
+                // Fixing the sign of the first value skips factors that only differ in sign
+                Some(match i {
+                    0 => divisors,
+                    _ => divisors.into_iter().flat_map(|x| [x, -x]).collect(),
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let count = choices
+            .iter()
+            .try_fold(1_usize, |acc, x| acc.checked_mul(x.len()));
+        if count.map(|x| x > MAX_COMBINATIONS).unwrap_or(true) {
+            continue;
+        }
+
+        let mut index = vec![0; choices.len()];
+        loop {
+            let ys = index
+                .iter()
+                .zip(&choices)
+                .map(|(i, x)| x[*i])
+                .collect::<Vec<_>>();
+            if let Some(mut g) = interpolate(&xs, &ys) {
+                if g.len() == d + 1 && div_exact(f, &g).is_some() {
+                    if g[d] < 0 {
+                        g.iter_mut().for_each(|x| *x = -*x);
+                    }
+                    return Some(g);
+                }
+            }
+
+            // Next combination
+            let mut i = 0;
+            while i < index.len() {
+                index[i] += 1;
+                if index[i] < choices[i].len() {
+                    break;
+                }
+                index[i] = 0;
+                i += 1;
+            }
+            if i == index.len() {
+                break;
+            }
+        }
+    }
+
+    None
+}
+
+// Polynomial through the points using Newton's divided differences,
+// None unless every coefficient is an integer
+fn interpolate(xs: &[i128], ys: &[i128]) -> Option<Vec<i128>> {
+    let n = xs.len();
+    let mut diffs = ys.iter().map(|y| (*y, 1_i128)).collect::<Vec<_>>();
+    for level in 1..n {
+        for i in (level..n).rev() {
+            let (a, b) = (diffs[i], diffs[i - 1]);
+            let num = a.0.checked_mul(b.1)?.checked_sub(b.0.checked_mul(a.1)?)?;
+            let den = a.1.checked_mul(b.1)?.checked_mul(xs[i] - xs[i - level])?;
+            diffs[i] = reduce(num, den);
+        }
+    }
+
+    // Expand c0 + c1 (x - x0) + c2 (x - x0)(x - x1) + ... from the innermost term
+    let mut out = vec![(0_i128, 1_i128); n];
+    for i in (0..n).rev() {
+        // out = out * (x - xs[i]) + diffs[i]
+        let mut next = vec![(0_i128, 1_i128); n];
+        for (j, c) in out.iter().enumerate() {
+            if j + 1 < n {
+                next[j + 1] = add(next[j + 1], *c)?;
+            }
+            next[j] = add(next[j], (c.0.checked_mul(-xs[i])?, c.1))?;
+        }
+        next[0] = add(next[0], diffs[i])?;
+        out = next;
+    }
+
+    let mut out = out
+        .into_iter()
+        .map(|(num, den)| (den == 1).then_some(num))
+        .collect::<Option<Vec<_>>>()?;
+    while out.len() > 1 && out.last() == Some(&0) {
+        out.pop();
+    }
+
+    Some(out)
+}
+
+fn add(a: (i128, i128), b: (i128, i128)) -> Option<(i128, i128)> {
+    let num = a.0.checked_mul(b.1)?.checked_add(b.0.checked_mul(a.1)?)?;
+    Some(reduce(num, a.1.checked_mul(b.1)?))
+}
+
+fn reduce(num: i128, den: i128) -> (i128, i128) {
+    let g = gcd(num, den).max(1) * den.signum();
+    (num / g, den / g)
+}
+
+// Positive divisors, None for numbers too big to search
+fn divisors(n: i128) -> Option<Vec<i128>> {
+    let n = n.abs();
+    if n > MAX_DIVISORS_OF {
+        return None;
+    }
+
+    let mut low = Vec::new();
+    let mut high = Vec::new();
+    let mut i = 1;
+    while i * i <= n {
+        if n % i == 0 {
+            low.push(i);
+            if i * i != n {
+                high.push(n / i);
+            }
+        }
+        i += 1;
+    }
+
+    low.extend(high.into_iter().rev());
+    Some(low)
+}
+
+pub fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Coefficients lowest power first, as `Polynomial` keeps them
+    fn poly(coeffs: &[Num]) -> Polynomial {
+        Polynomial(coeffs.to_vec())
+    }
+
+    // Compares in order of the real then the imaginary part
+    fn assert_roots(mut actual: Vec<Complex>, expected: &[(Num, Num)]) {
+        actual.sort_by(|a, b| a.re.total_cmp(&b.re).then(a.im.total_cmp(&b.im)));
+        assert_eq!(actual.len(), expected.len(), "got {actual:?}");
+        for (root, (re, im)) in actual.iter().zip(expected) {
+            assert!(
+                (root.re - re).abs() < 1e-10 && (root.im - im).abs() < 1e-10,
+                "got {actual:?}, expected {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_factor() {
+        let (content, factors) = factor(&poly(&[-1., 0., 1.])).unwrap();
+        assert_eq!(content, 1.);
+        assert_eq!(factors, [(vec![-1, 1], 1), (vec![1, 1], 1)]);
+
+        // No rational roots, so the quadratic factors come from Kronecker's method
+        let (_, factors) = factor(&poly(&[4., 0., 0., 0., 1.])).unwrap();
+        assert_eq!(factors, [(vec![2, -2, 1], 1), (vec![2, 2, 1], 1)]);
+
+        let (content, factors) = factor(&poly(&[2., -4., 2.])).unwrap();
+        assert_eq!(content, 2.);
+        assert_eq!(factors, [(vec![-1, 1], 2)]);
+
+        let (_, factors) = factor(&poly(&[1., 0., 1.])).unwrap();
+        assert_eq!(factors, [(vec![1, 0, 1], 1)]);
+    }
+
+    #[test]
+    fn test_roots() {
+        assert_roots(roots(&poly(&[1., 0., 1.])), &[(0., -1.), (0., 1.)]);
+        assert_roots(
+            roots(&poly(&[-6., 11., -6., 1.])),
+            &[(1., 0.), (2., 0.), (3., 0.)],
+        );
+
+        // Irrational coefficients skip factoring and go straight to Aberth's method
+        let root = (2. / std::f64::consts::PI).sqrt();
+        assert_roots(
+            roots(&poly(&[-2., 0., std::f64::consts::PI])),
+            &[(-root, 0.), (root, 0.)],
+        );
+    }
+
+    #[test]
+    fn test_div_rem() {
+        // (x^3 + 2x + 1) / (x^2 + 1) is x, remainder x + 1
+        let (quotient, remainder) = poly(&[1., 2., 0., 1.]).div_rem(&poly(&[1., 0., 1.]));
+        assert_eq!(quotient.0, [0., 1.]);
+        assert_eq!(remainder.0, [1., 1.]);
+
+        let (quotient, remainder) = poly(&[-1., 0., 1.]).div_rem(&poly(&[-1., 1.]));
+        assert_eq!(quotient.0, [1., 1.]);
+        assert!(remainder.is_zero());
+    }
+}
@@ -0,0 +1,264 @@
+//! Root finding.
+
+use super::{reqire_args_range, var_arg, Function};
+use crate::calc::{solver::Context, Error, Num, Ops, Result, Token};
+
+const EPSILON: Num = 1e-15;
+const MAX_ITER: usize = 200;
+// Subintervals `roots` checks for sign changes by default
+const ROOT_SAMPLES: usize = 1000;
+
+pub struct Solve;
+impl Function for Solve {
+    fn name(&self) -> &'static str {
+        "solve"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    // (expr, var, guess) or (expr, var, a, b), `expr` can be an equation
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        reqire_args_range(self.name(), &args, 3, 4)?;
+        let var = var_arg(self.name(), &args[1])?;
+        let expr = equation_to_expr(&args[0]);
+        let bounds = args[2..]
+            .iter()
+            .map(|x| context.evaluate(x.to_owned()))
+            .collect::<Result<Vec<_>>>()?;
+        let mut f = |x| context.evaluate_with(&expr, &var, x);
+
+        let root = match bounds[..] {
+            [guess] => match newton(guess, &mut f)? {
+                Some(i) => Some(i),
+                None => match find_bracket(guess, &mut f)? {
+                    Some((a, b)) => brent(a, b, &mut f)?,
+                    None => None,
+                },
+            },
+            [a, b] => brent(a, b, &mut f)?,
+            _ => unreachable!(),
+        };
+
+        root.ok_or_else(|| Error::NoConvergence(self.name().to_owned()))
+    }
+}
+
+pub struct Roots;
+impl Function for Roots {
+    fn name(&self) -> &'static str {
+        "roots"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    // (expr, var, a, b, [samples])
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args_range(self.name(), &args, 4, 5)?;
+        let var = var_arg(self.name(), &args[1])?;
+        let expr = equation_to_expr(&args[0]);
+        let a = context.evaluate(args[2].to_owned())?;
+        let b = context.evaluate(args[3].to_owned())?;
+        let samples = match args.get(4) {
+            Some(i) => context.evaluate(i.to_owned())?,
+            None => ROOT_SAMPLES as Num,
+        };
+        if !(a.is_finite() && b.is_finite() && a < b) || samples < 1. {
+            return Err(Error::InvalidArgument(
+                self.name().to_owned(),
+                "expected a finite interval with a < b".to_owned(),
+            ));
+        }
+
+        let mut f = |x| context.evaluate_with(&expr, &var, x);
+        let step = (b - a) / samples.floor();
+        let mut out: Vec<Num> = Vec::new();
+        let mut prev = (a, f(a)?);
+        for i in 1..=samples as usize {
+            let x = if i == samples as usize {
+                b
+            } else {
+                a + step * i as Num
+            };
+            let cur = (x, f(x)?);
+
+            let root = match (prev.1, cur.1) {
+                (0., _) => Some(prev.0),
+                (l, r) if l.signum() != r.signum() && l.is_finite() && r.is_finite() => {
+                    brent(prev.0, cur.0, &mut f)?
+                }
+                _ => None,
+            };
+
+            if let Some(root) = root {
+                if out
+                    .last()
+                    .map(|x| (x - root).abs() > step / 2.)
+                    .unwrap_or(true)
+                {
+                    out.push(root);
+                }
+            }
+            prev = cur;
+        }
+
+        if prev.1 == 0. && out.last() != Some(&prev.0) {
+            out.push(prev.0);
+        }
+
+        Ok(Token::List(out.into_iter().map(Token::Number).collect()))
+    }
+}
+
+/// `lhs = rhs` becomes `lhs - rhs`, other expressions are left as they are.
+pub fn equation_to_expr(token: &Token) -> Token {
+    match token {
+        Token::Tree(Ops::Equal, left, right) => {
+            Token::Tree(Ops::Sub, left.to_owned(), right.to_owned())
+        }
+        i => i.to_owned(),
+    }
+}
+
+/// Newton's method with a central difference derivative.
+/// Returns None if it does not converge to a root.
+pub fn newton(guess: Num, f: &mut impl FnMut(Num) -> Result<Num>) -> Result<Option<Num>> {
+    let mut x = guess;
+    let scale = f(guess)?.abs().max(1.);
+    for _ in 0..MAX_ITER {
+        let y = f(x)?;
+        if y == 0. {
+            return Ok(Some(x));
+        }
+
+        let h = Num::EPSILON.cbrt() * x.abs().max(1.);
+        let dy = (f(x + h)? - f(x - h)?) / (2. * h);
+        let step = y / dy;
+        if !step.is_finite() {
+            return Ok(None);
+        }
+
+        x -= step;
+        if step.abs() <= EPSILON.sqrt() * x.abs().max(1.) {
+            return Ok(is_root(x, scale, f)?.then_some(x));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Brent's method, needs f(a) and f(b) to have opposite signs.
+/// Returns None if there is no sign change or the sign change is not a root (like a pole).
+pub fn brent(
+    mut a: Num,
+    mut b: Num,
+    f: &mut impl FnMut(Num) -> Result<Num>,
+) -> Result<Option<Num>> {
+    let (mut fa, mut fb) = (f(a)?, f(b)?);
+    if fa == 0. {
+        return Ok(Some(a));
+    }
+    if fb == 0. {
+        return Ok(Some(b));
+    }
+    if fa.signum() == fb.signum() || fa.is_nan() || fb.is_nan() {
+        return Ok(None);
+    }
+
+    let scale = fa.abs().max(fb.abs()).max(1.);
+    let (mut c, mut fc) = (a, fa);
+    let (mut d, mut e) = (b - a, b - a);
+    for _ in 0..MAX_ITER {
+        if fb.signum() == fc.signum() {
+            (c, fc) = (a, fa);
+            d = b - a;
+            e = d;
+        }
+        if fc.abs() < fb.abs() {
+            (a, fa) = (b, fb);
+            (b, fb) = (c, fc);
+            (c, fc) = (a, fa);
+        }
+
+        let tol = 2. * Num::EPSILON * b.abs() + EPSILON / 2.;
+        let m = (c - b) / 2.;
+        if m.abs() <= tol || fb == 0. {
+            return Ok(is_root(b, scale, f)?.then_some(b));
+        }
+
+        if e.abs() >= tol && fa.abs() > fb.abs() {
+            // Inverse quadratic interpolation, or the secant method when a == c
+            let s = fb / fa;
+            let (mut p, mut q) = if a == c {
+                (2. * m * s, 1. - s)
+            } else {
+                let q = fa / fc;
+                let r = fb / fc;
+                (
+                    s * (2. * m * q * (q - r) - (b - a) * (r - 1.)),
+                    (q - 1.) * (r - 1.) * (s - 1.),
+                )
+            };
+            if p > 0. {
+                q = -q;
+            } else {
+                p = -p;
+            }
+
+            if 2. * p < (3. * m * q - (tol * q).abs()).min((e * q).abs()) {
+                e = d;
+                d = p / q;
+            } else {
+                d = m;
+                e = m;
+            }
+        } else {
+            d = m;
+            e = m;
+        }
+
+        (a, fa) = (b, fb);
+        b += if d.abs() > tol { d } else { tol.copysign(m) };
+        fb = f(b)?;
+    }
+
+    Ok(None)
+}
+
+// Looks outwards from the guess for a sign change
+fn find_bracket(guess: Num, f: &mut impl FnMut(Num) -> Result<Num>) -> Result<Option<(Num, Num)>> {
+    let start = f(guess)?;
+    let mut step = 0.1 * guess.abs().max(1.);
+    for _ in 0..MAX_ITER / 2 {
+        for x in [guess - step, guess + step] {
+            let y = f(x)?;
+            if y.is_finite() && y.signum() != start.signum() {
+                return Ok(Some(if x < guess { (x, guess) } else { (guess, x) }));
+            }
+        }
+        step *= 1.6;
+    }
+
+    Ok(None)
+}
+
+// Rejects sign changes at poles and points where newton stalled
+fn is_root(x: Num, scale: Num, f: &mut impl FnMut(Num) -> Result<Num>) -> Result<bool> {
+    let y = f(x)?;
+    Ok(y.is_finite() && y.abs() <= EPSILON.sqrt() * scale)
+}
@@ -0,0 +1,285 @@
+//! Summation and product notation, and lists of terms.
+
+use std::collections::VecDeque;
+
+use super::{
+    calculus::{converged, integrate},
+    reqire_args_range, var_arg, Function,
+};
+use crate::calc::{solver::Context, Error, Num, Result, Token};
+
+// Default tolerance for infinite series
+const TOLERANCE: Num = 1e-10;
+const MAX_TERMS: usize = 10_000_000;
+// Consecutive small terms needed before an infinite series is considered converged, terms are
+// scaled by their index so slowly decaying series are not cut off early
+const SMALL_TERMS: usize = 10;
+// Partial sums averaged for alternating series
+const AVERAGED_SUMS: usize = 20;
+// Terms summed before estimating the rest of a series with an integral
+const TAIL_START: usize = 10_000;
+// Longest list `seq` makes
+const MAX_SEQ_TERMS: Num = 100_000.;
+
+pub struct Sum;
+impl Function for Sum {
+    fn name(&self) -> &'static str {
+        "sum"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    // (expr, var, from, to, [tolerance])
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        let series = Series::new(self.name(), args, context)?;
+        series.sum(context)
+    }
+}
+
+pub struct Prod;
+impl Function for Prod {
+    fn name(&self) -> &'static str {
+        "prod"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    // (expr, var, from, to, [tolerance])
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        let series = Series::new(self.name(), args, context)?;
+        series.prod(context)
+    }
+}
+
+pub struct Seq;
+impl Function for Seq {
+    fn name(&self) -> &'static str {
+        "seq"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    // (expr, var, from, to, [step])
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args_range(self.name(), &args, 4, 5)?;
+        let var = var_arg(self.name(), &args[1])?;
+        let from = context.evaluate(args[2].to_owned())?;
+        let to = context.evaluate(args[3].to_owned())?;
+        let step = match args.get(4) {
+            Some(i) => context.evaluate(i.to_owned())?,
+            None => 1.,
+        };
+
+        let count = ((to - from) / step).floor() + 1.;
+        if !count.is_finite() || step == 0. {
+            return Err(Error::InvalidArgument(
+                self.name().to_owned(),
+                "expected a finite range and a step that is not zero".to_owned(),
+            ));
+        }
+        if count > MAX_SEQ_TERMS {
+            return Err(Error::InvalidArgument(
+                self.name().to_owned(),
+                format!("can make at most {} terms", MAX_SEQ_TERMS),
+            ));
+        }
+
+        // Steps are multiplied instead of added up, so rounding does not build up
+        (0..count.max(0.) as usize)
+            .map(|i| {
+                let x = from + step * i as Num;
+                context.evaluate_with(&args[0], &var, x).map(Token::Number)
+            })
+            .collect::<Result<_>>()
+            .map(Token::List)
+    }
+}
+
+struct Series {
+    name: &'static str,
+    expr: Token,
+    var: String,
+    from: Num,
+    to: Num,
+    tol: Num,
+}
+
+impl Series {
+    fn new(name: &'static str, args: Vec<Token>, context: &mut Context) -> Result<Self> {
+        reqire_args_range(name, &args, 4, 5)?;
+        let var = var_arg(name, &args[1])?;
+        let from = context.evaluate(args[2].to_owned())?;
+        let to = context.evaluate(args[3].to_owned())?;
+        let tol = match args.get(4) {
+            Some(i) => context.evaluate(i.to_owned())?,
+            None => TOLERANCE,
+        };
+
+        if !from.is_finite() || to.is_nan() || to == Num::NEG_INFINITY {
+            return Err(Error::InvalidArgument(
+                name.to_owned(),
+                "the range must start at a finite number".to_owned(),
+            ));
+        }
+
+        if to.is_finite() && to - from > MAX_TERMS as Num {
+            return Err(Error::InvalidArgument(
+                name.to_owned(),
+                format!("the range can have at most {} terms", MAX_TERMS),
+            ));
+        }
+
+        Ok(Self {
+            name,
+            expr: args[0].to_owned(),
+            var,
+            from,
+            to,
+            tol,
+        })
+    }
+
+    fn term(&self, context: &mut Context, k: Num) -> Result<Num> {
+        context.evaluate_with(&self.expr, &self.var, k)
+    }
+
+    fn sum(&self, context: &mut Context) -> Result<Num> {
+        if self.to.is_finite() {
+            return self.fold(context, 0., |acc, x| acc + x);
+        }
+
+        self.infinite(context, |x| x)
+    }
+
+    // Infinite products are summed as logarithms, so their factors need to be positive
+    fn prod(&self, context: &mut Context) -> Result<Num> {
+        if self.to.is_finite() {
+            return self.fold(context, 1., |acc, x| acc * x);
+        }
+
+        Ok(self.infinite(context, Num::ln)?.exp())
+    }
+
+    fn fold(&self, context: &mut Context, init: Num, f: impl Fn(Num, Num) -> Num) -> Result<Num> {
+        let mut acc = init;
+        let mut k = self.from;
+        while k <= self.to {
+            acc = f(acc, self.term(context, k)?);
+            k += 1.;
+        }
+
+        Ok(acc)
+    }
+
+    // Sums `g(term)` up to infinity. Stops once the terms are small, when the partial sums of an
+    // alternating series settle, or when the remaining terms can be estimated with an integral.
+    fn infinite(&self, context: &mut Context, g: impl Fn(Num) -> Num) -> Result<Num> {
+        let mut acc = 0.;
+        let mut prev: Num = 0.;
+        let mut small = 0;
+        let mut alternating = 0;
+        let mut partial = VecDeque::with_capacity(AVERAGED_SUMS);
+        let mut estimate = Num::NAN;
+        let mut k = self.from;
+        for i in 1..=MAX_TERMS {
+            let term = g(self.term(context, k)?);
+            acc += term;
+            if !acc.is_finite() {
+                break;
+            }
+
+            let scale = self.tol * acc.abs().max(1.);
+            match term.abs() * i as Num <= scale {
+                true => small += 1,
+                false => small = 0,
+            }
+            if small >= SMALL_TERMS {
+                return Ok(acc);
+            }
+
+            match term.signum() != prev.signum() && term != 0. {
+                true => alternating += 1,
+                false => alternating = 0,
+            }
+            if partial.len() == AVERAGED_SUMS {
+                partial.pop_front();
+            }
+            partial.push_back(acc);
+            if alternating >= AVERAGED_SUMS {
+                let next = average(&partial);
+                if (next - estimate).abs() <= scale {
+                    return Ok(next);
+                }
+                estimate = next;
+            }
+
+            if i == TAIL_START && alternating == 0 {
+                if let Some(tail) = self.tail(context, k + 1., &g)? {
+                    return Ok(acc + tail);
+                }
+            }
+
+            prev = term;
+            k += 1.;
+        }
+
+        Err(Error::NoConvergence(self.name.to_owned()))
+    }
+
+    // Estimates Σ g(term(k)) for k >= start as ∫ g(term(x)) dx from start - 1/2 to infinity,
+    // substituting x = a / u to integrate over (0, 1]. Returns None for terms that are not
+    // defined between integers.
+    fn tail(
+        &self,
+        context: &mut Context,
+        start: Num,
+        g: impl Fn(Num) -> Num,
+    ) -> Result<Option<Num>> {
+        let a = start - 0.5;
+        let tail = integrate(0., 1., self.tol, |u| {
+            Ok(match g(self.term(context, a / u)?) {
+                0. => 0.,
+                y => y * a / (u * u),
+            })
+        });
+
+        match tail {
+            Ok((value, _)) if value.is_nan() => Ok(None),
+            Ok((value, error)) if converged(value, error, self.tol) => Ok(Some(value)),
+            Ok(_) => Err(Error::NoConvergence(self.name.to_owned())),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+// Repeatedly averages neighbouring partial sums, which quickly converges for alternating series
+fn average(partial: &VecDeque<Num>) -> Num {
+    let mut sums = partial.iter().copied().collect::<Vec<_>>();
+    while sums.len() > 1 {
+        sums = sums.windows(2).map(|x| (x[0] + x[1]) / 2.).collect();
+    }
+
+    sums[0]
+}
@@ -0,0 +1,379 @@
+//! Signal processing on lists of real or complex numbers.
+//!
+//! Complex results are given as complex values, which can be stored and passed back in, and
+//! `complex(re, im)` makes one by hand. Parts that are only rounding error are snapped to zero,
+//! so the inverse transform of a real signal comes back real.
+
+use std::f64::consts::{PI, TAU};
+
+use super::{reqire_args, var_arg, Function};
+use crate::calc::{complex::Complex, solver::Context, Error, Num, Result, Token};
+
+// Parts smaller than this, relative to the largest value, are treated as rounding error
+const EPSILON: Num = 1e-12;
+const MAX_WINDOW: Num = 1e7;
+
+pub struct Fft;
+impl Function for Fft {
+    fn name(&self) -> &'static str {
+        "fft"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args(self.name(), &args, 1)?;
+        let values = complex_list(self.name(), &args[0], context)?;
+        Ok(to_list(fft(&values)))
+    }
+}
+
+pub struct Ifft;
+impl Function for Ifft {
+    fn name(&self) -> &'static str {
+        "ifft"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args(self.name(), &args, 1)?;
+        let values = complex_list(self.name(), &args[0], context)?;
+        Ok(to_list(ifft(&values)))
+    }
+}
+
+pub struct Convolve;
+impl Function for Convolve {
+    fn name(&self) -> &'static str {
+        "convolve"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    // (a, b), the full convolution with `len(a) + len(b) - 1` values
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args(self.name(), &args, 2)?;
+        let a = complex_list(self.name(), &args[0], context)?;
+        let b = complex_list(self.name(), &args[1], context)?;
+        Ok(to_list(convolve(&a, &b)))
+    }
+}
+
+pub struct Correlate;
+impl Function for Correlate {
+    fn name(&self) -> &'static str {
+        "correlate"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    // (a, b), the full cross correlation, starting with `b` shifted to end on the first value
+    // of `a`
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args(self.name(), &args, 2)?;
+        let a = complex_list(self.name(), &args[0], context)?;
+        let b = complex_list(self.name(), &args[1], context)?;
+        let b = b.iter().rev().map(|x| x.conj()).collect::<Vec<_>>();
+        Ok(to_list(convolve(&a, &b)))
+    }
+}
+
+pub struct Window;
+impl Function for Window {
+    fn name(&self) -> &'static str {
+        "window"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    // (hann / hamming / blackman / rect, n), symmetric windows of length `n`
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args(self.name(), &args, 2)?;
+        let kind = var_arg(self.name(), &args[0])?;
+        let n = context.evaluate(args[1].to_owned())?;
+        if n.fract() != 0. || !(1. ..=MAX_WINDOW).contains(&n) {
+            return Err(Error::InvalidArgument(
+                self.name().to_owned(),
+                format!("the length must be a whole number from 1 to {}", MAX_WINDOW),
+            ));
+        }
+
+        let window: fn(Num) -> Num = match kind.as_str() {
+            "hann" => |x| 0.5 - 0.5 * (TAU * x).cos(),
+            "hamming" => |x| 0.54 - 0.46 * (TAU * x).cos(),
+            "blackman" => |x| 0.42 - 0.5 * (TAU * x).cos() + 0.08 * (2. * TAU * x).cos(),
+            "rect" => |_| 1.,
+            i => {
+                return Err(Error::InvalidArgument(
+                    self.name().to_owned(),
+                    format!(
+                        "unknown window `{}`, expected `hann`, `hamming`, `blackman` or `rect`",
+                        i
+                    ),
+                ))
+            }
+        };
+
+        // A single point has nothing to taper towards
+        let n = n as usize;
+        Ok(Token::List(
+            (0..n)
+                .map(|i| match n {
+                    1 => 1.,
+                    n => window(i as Num / (n - 1) as Num),
+                })
+                .map(|x| Token::Number(snap(x, 1.)))
+                .collect(),
+        ))
+    }
+}
+
+pub struct Magnitude;
+impl Function for Magnitude {
+    fn name(&self) -> &'static str {
+        "magnitude"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args(self.name(), &args, 1)?;
+        map_complex(self.name(), &args[0], |x| x.abs(), context)
+    }
+}
+
+pub struct Phase;
+impl Function for Phase {
+    fn name(&self) -> &'static str {
+        "phase"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args(self.name(), &args, 1)?;
+        map_complex(self.name(), &args[0], |x| x.arg(), context)
+    }
+}
+
+pub struct MakeComplex;
+impl Function for MakeComplex {
+    fn name(&self) -> &'static str {
+        "complex"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        self.call_value(args, context)?.as_number()
+    }
+
+    // (re, im)
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args(self.name(), &args, 2)?;
+        let re = context.evaluate(args[0].to_owned())?;
+        let im = context.evaluate(args[1].to_owned())?;
+        Ok(to_token(Complex::new(re, im)))
+    }
+}
+
+fn complex_value(name: &str, token: Token, context: &mut Context) -> Result<Complex> {
+    match token {
+        Token::Number(i) => Ok(Complex::from(i)),
+        Token::Complex(i) => Ok(i),
+        Token::List(_) => Err(Error::InvalidArgument(
+            name.to_owned(),
+            "expected a number, got a list".to_owned(),
+        )),
+        i => context.evaluate(i).map(Complex::from),
+    }
+}
+
+fn complex_list(name: &str, arg: &Token, context: &mut Context) -> Result<Vec<Complex>> {
+    match context.evaluate_value(arg.to_owned())? {
+        Token::List(items) if items.is_empty() => Err(Error::InvalidArgument(
+            name.to_owned(),
+            "the list is empty".to_owned(),
+        )),
+        Token::List(items) => items
+            .into_iter()
+            .map(|x| complex_value(name, x, context))
+            .collect(),
+        i => Err(Error::InvalidArgument(
+            name.to_owned(),
+            format!("expected a list, got `{}`", i),
+        )),
+    }
+}
+
+// Applies `func` to a single value or to every value of a list
+fn map_complex(
+    name: &str,
+    arg: &Token,
+    func: fn(Complex) -> Num,
+    context: &mut Context,
+) -> Result<Token> {
+    match context.evaluate_value(arg.to_owned())? {
+        Token::List(items) => Ok(Token::List(
+            items
+                .into_iter()
+                .map(|x| complex_value(name, x, context).map(|x| Token::Number(func(x))))
+                .collect::<Result<_>>()?,
+        )),
+        i => complex_value(name, i, context).map(|x| Token::Number(func(x))),
+    }
+}
+
+fn snap(x: Num, scale: Num) -> Num {
+    match x.abs() <= EPSILON * scale {
+        true => 0.,
+        false => x,
+    }
+}
+
+fn to_token(x: Complex) -> Token {
+    match x.im {
+        0. => Token::Number(x.re),
+        _ => Token::Complex(x),
+    }
+}
+
+fn to_list(values: Vec<Complex>) -> Token {
+    let scale = values.iter().map(|x| x.abs()).fold(0., Num::max);
+    Token::List(
+        values
+            .into_iter()
+            .map(|x| to_token(Complex::new(snap(x.re, scale), snap(x.im, scale))))
+            .collect(),
+    )
+}
+
+/// Discrete Fourier transform, `X[k] = Σ x[n] e^(-2πikn/N)`.
+pub fn fft(values: &[Complex]) -> Vec<Complex> {
+    match values.len().is_power_of_two() {
+        true => radix_2(values),
+        false => bluestein(values),
+    }
+}
+
+/// Inverse of `fft`, including the `1/N` scaling.
+pub fn ifft(values: &[Complex]) -> Vec<Complex> {
+    let n = values.len() as Num;
+    let conj = values.iter().map(|x| x.conj()).collect::<Vec<_>>();
+    fft(&conj)
+        .into_iter()
+        .map(|x| Complex::new(x.re / n, -x.im / n))
+        .collect()
+}
+
+// Cooley-Tukey, only for lengths that are powers of two
+fn radix_2(values: &[Complex]) -> Vec<Complex> {
+    let n = values.len();
+    if n == 1 {
+        return values.to_vec();
+    }
+
+    let even = radix_2(&values.iter().step_by(2).copied().collect::<Vec<_>>());
+    let odd = radix_2(
+        &values
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .copied()
+            .collect::<Vec<_>>(),
+    );
+    let mut out = vec![Complex::from(0.); n];
+    for k in 0..n / 2 {
+        let t = Complex::from_polar(1., -TAU * k as Num / n as Num) * odd[k];
+        out[k] = even[k] + t;
+        out[k + n / 2] = even[k] - t;
+    }
+
+    out
+}
+
+// Bluestein's algorithm turns a transform of any length into a convolution, which is done with
+// power of two transforms
+fn bluestein(values: &[Complex]) -> Vec<Complex> {
+    let n = values.len();
+    let m = (2 * n - 1).next_power_of_two();
+
+    // e^(-πik²/N), reducing k² first since only it modulo 2N matters
+    let chirp = (0..n)
+        .map(|k| Complex::from_polar(1., -PI * ((k * k) % (2 * n)) as Num / n as Num))
+        .collect::<Vec<_>>();
+
+    let mut a = vec![Complex::from(0.); m];
+    let mut b = vec![Complex::from(0.); m];
+    for k in 0..n {
+        a[k] = values[k] * chirp[k];
+        b[k] = chirp[k].conj();
+        b[(m - k) % m] = chirp[k].conj();
+    }
+
+    let product = radix_2(&a)
+        .into_iter()
+        .zip(radix_2(&b))
+        .map(|(a, b)| (a * b).conj())
+        .collect::<Vec<_>>();
+    let conv = radix_2(&product);
+
+    (0..n)
+        .map(|k| conv[k].conj() * chirp[k] / Complex::from(m as Num))
+        .collect()
+}
+
+fn convolve(a: &[Complex], b: &[Complex]) -> Vec<Complex> {
+    let mut out = vec![Complex::from(0.); a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            out[i + j] = out[i + j] + *x * *y;
+        }
+    }
+    out
+}
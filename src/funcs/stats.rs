@@ -0,0 +1,535 @@
+//! Probability distributions.
+//!
+//! Every distribution has a density / mass function, a cumulative distribution function and a
+//! quantile function. Discrete quantiles return the smallest value whose cdf is at least `p`.
+
+use std::f64::consts::{PI, SQRT_2};
+
+use super::{
+    reqire_args_range,
+    special::{beta_inc, erfc, gamma_p, gamma_q, lgamma},
+    Function,
+};
+use crate::calc::{solver::Context, Error, Num, Result, Token};
+
+const MAX_ITER: usize = 2000;
+
+macro_rules! stat_func {
+    ($name:ident, $str:expr, $defaults:expr, $func:ident) => {
+        pub struct $name;
+
+        impl Function for $name {
+            fn name(&self) -> &'static str {
+                $str
+            }
+
+            fn is_pure(&self) -> bool {
+                true
+            }
+
+            fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+                let args = eval_args(self.name(), args, context, &$defaults)?;
+                $func(self.name(), &args)
+            }
+        }
+    };
+}
+
+// == Normal ==
+stat_func!(NormPdf, "normpdf", [None, Some(0.), Some(1.)], norm_pdf);
+stat_func!(NormCdf, "normcdf", [None, Some(0.), Some(1.)], norm_cdf);
+stat_func!(NormInv, "norminv", [None, Some(0.), Some(1.)], norm_inv);
+
+// == Student t ==
+stat_func!(TPdf, "tpdf", [None, None], t_pdf);
+stat_func!(TCdf, "tcdf", [None, None], t_cdf);
+stat_func!(TInv, "tinv", [None, None], t_inv);
+
+// == Chi-squared ==
+stat_func!(ChiSqPdf, "chisqpdf", [None, None], chisq_pdf);
+stat_func!(ChiSqCdf, "chisqcdf", [None, None], chisq_cdf);
+stat_func!(ChiSqInv, "chisqinv", [None, None], chisq_inv);
+
+// == Exponential ==
+stat_func!(ExpPdf, "exppdf", [None, Some(1.)], exp_pdf);
+stat_func!(ExpCdf, "expcdf", [None, Some(1.)], exp_cdf);
+stat_func!(ExpInv, "expinv", [None, Some(1.)], exp_inv);
+
+// == Binomial ==
+stat_func!(BinomPmf, "binompmf", [None, None, None], binom_pmf);
+stat_func!(BinomCdf, "binomcdf", [None, None, None], binom_cdf);
+stat_func!(BinomInv, "binominv", [None, None, None], binom_inv);
+
+// == Poisson ==
+stat_func!(PoissPmf, "poisspmf", [None, None], poiss_pmf);
+stat_func!(PoissCdf, "poisscdf", [None, None], poiss_cdf);
+stat_func!(PoissInv, "poissinv", [None, None], poiss_inv);
+
+// == Uniform ==
+stat_func!(UnifPdf, "unifpdf", [None, Some(0.), Some(1.)], unif_pdf);
+stat_func!(UnifCdf, "unifcdf", [None, Some(0.), Some(1.)], unif_cdf);
+stat_func!(UnifInv, "unifinv", [None, Some(0.), Some(1.)], unif_inv);
+
+// == Geometric (number of trials up to and including the first success) ==
+stat_func!(GeomPmf, "geompmf", [None, None], geom_pmf);
+stat_func!(GeomCdf, "geomcdf", [None, None], geom_cdf);
+stat_func!(GeomInv, "geominv", [None, None], geom_inv);
+
+// Evaluates the arguments, filling in defaults for missing trailing ones.
+// A default of None marks a required argument.
+fn eval_args(
+    name: &str,
+    args: Vec<Token>,
+    context: &mut Context,
+    defaults: &[Option<Num>],
+) -> Result<Vec<Num>> {
+    let required = defaults.iter().filter(|x| x.is_none()).count();
+    reqire_args_range(name, &args, required, defaults.len())?;
+
+    let mut out = args
+        .into_iter()
+        .map(|x| context.evaluate(x))
+        .collect::<Result<Vec<_>>>()?;
+    out.extend(defaults[out.len()..].iter().map(|x| x.unwrap()));
+    Ok(out)
+}
+
+fn check(name: &str, cond: bool, msg: &str) -> Result<()> {
+    if !cond {
+        return Err(Error::InvalidArgument(name.to_owned(), msg.to_owned()));
+    }
+
+    Ok(())
+}
+
+fn check_prob(name: &str, p: Num) -> Result<()> {
+    check(
+        name,
+        (0. ..=1.).contains(&p),
+        "probability must be in [0, 1]",
+    )
+}
+
+fn check_positive(name: &str, x: Num, what: &str) -> Result<()> {
+    check(name, x > 0., &format!("{} must be positive", what))
+}
+
+// Finds x where cdf(x) = p with bisection, the bracket is grown until it contains p
+fn invert(p: Num, cdf: impl Fn(Num) -> Num, mut lo: Num, mut hi: Num, bounded_lo: bool) -> Num {
+    while !bounded_lo && cdf(lo) > p {
+        lo = lo * 2. - 1.;
+    }
+    while cdf(hi) < p {
+        hi = hi * 2. + 1.;
+    }
+
+    for _ in 0..MAX_ITER {
+        let mid = lo + (hi - lo) / 2.;
+        if mid <= lo || mid >= hi {
+            break;
+        }
+
+        match cdf(mid) < p {
+            true => lo = mid,
+            false => hi = mid,
+        }
+    }
+
+    lo + (hi - lo) / 2.
+}
+
+// Smallest integer k >= lo with cdf(k) >= p
+fn invert_discrete(p: Num, cdf: impl Fn(Num) -> Num, mut lo: Num, hi: Option<Num>) -> Num {
+    let mut hi = hi.unwrap_or(lo + 1.);
+    while cdf(hi) < p {
+        if hi > Num::MAX / 4. {
+            return Num::INFINITY;
+        }
+        lo = hi;
+        hi = hi * 2. + 1.;
+    }
+
+    if cdf(lo) >= p {
+        return lo;
+    }
+
+    while hi - lo > 1. {
+        let mid = (lo + (hi - lo) / 2.).floor();
+        match cdf(mid) < p {
+            true => lo = mid,
+            false => hi = mid,
+        }
+    }
+
+    hi
+}
+
+fn norm_pdf(name: &str, a: &[Num]) -> Result<Num> {
+    check_positive(name, a[2], "sigma")?;
+    let z = (a[0] - a[1]) / a[2];
+    Ok((-0.5 * z * z).exp() / (a[2] * (2. * PI).sqrt()))
+}
+
+fn norm_cdf(name: &str, a: &[Num]) -> Result<Num> {
+    check_positive(name, a[2], "sigma")?;
+    Ok(std_norm_cdf((a[0] - a[1]) / a[2]))
+}
+
+fn norm_inv(name: &str, a: &[Num]) -> Result<Num> {
+    check_prob(name, a[0])?;
+    check_positive(name, a[2], "sigma")?;
+    Ok(a[1] + a[2] * std_norm_inv(a[0]))
+}
+
+fn std_norm_cdf(z: Num) -> Num {
+    0.5 * erfc(-z / SQRT_2)
+}
+
+// Acklam's rational approximation followed by a Halley step
+fn std_norm_inv(p: Num) -> Num {
+    const A: [Num; 6] = [
+        -3.969_683_028_665_376e1,
+        2.209_460_984_245_205e2,
+        -2.759_285_104_469_687e2,
+        1.383_577_518_672_69e2,
+        -3.066_479_806_614_716e1,
+        2.506_628_277_459_239,
+    ];
+    const B: [Num; 5] = [
+        -5.447_609_879_822_406e1,
+        1.615_858_368_580_409e2,
+        -1.556_989_798_598_866e2,
+        6.680_131_188_771_972e1,
+        -1.328_068_155_288_572e1,
+    ];
+    const C: [Num; 6] = [
+        -7.784_894_002_430_293e-3,
+        -3.223_964_580_411_365e-1,
+        -2.400_758_277_161_838,
+        -2.549_732_539_343_734,
+        4.374_664_141_464_968,
+        2.938_163_982_698_783,
+    ];
+    const D: [Num; 4] = [
+        7.784_695_709_041_462e-3,
+        3.224_671_290_700_398e-1,
+        2.445_134_137_142_996,
+        3.754_408_661_907_416,
+    ];
+    let poly = |c: &[Num], x: Num| c.iter().fold(0., |acc, c| acc * x + c);
+
+    match p {
+        0. => return Num::NEG_INFINITY,
+        1. => return Num::INFINITY,
+        _ => {}
+    }
+
+    let low = 0.02425;
+    let x = if p < low {
+        let q = (-2. * p.ln()).sqrt();
+        poly(&C, q) / (poly(&D, q) * q + 1.)
+    } else if p <= 1. - low {
+        let q = p - 0.5;
+        let r = q * q;
+        poly(&A, r) * q / (poly(&B, r) * r + 1.)
+    } else {
+        let q = (-2. * (1. - p).ln()).sqrt();
+        -poly(&C, q) / (poly(&D, q) * q + 1.)
+    };
+
+    let e = std_norm_cdf(x) - p;
+    let u = e * (2. * PI).sqrt() * (x * x / 2.).exp();
+    x - u / (1. + x * u / 2.)
+}
+
+fn t_pdf(name: &str, a: &[Num]) -> Result<Num> {
+    let (x, v) = (a[0], a[1]);
+    check_positive(name, v, "degrees of freedom")?;
+    Ok((lgamma((v + 1.) / 2.)
+        - lgamma(v / 2.)
+        - 0.5 * (v * PI).ln()
+        - (v + 1.) / 2. * (1. + x * x / v).ln())
+    .exp())
+}
+
+fn t_cdf(name: &str, a: &[Num]) -> Result<Num> {
+    check_positive(name, a[1], "degrees of freedom")?;
+    Ok(std_t_cdf(a[0], a[1]))
+}
+
+fn std_t_cdf(x: Num, v: Num) -> Num {
+    let tail = 0.5 * beta_inc(v / (v + x * x), v / 2., 0.5);
+    match x > 0. {
+        true => 1. - tail,
+        false => tail,
+    }
+}
+
+fn t_inv(name: &str, a: &[Num]) -> Result<Num> {
+    check_prob(name, a[0])?;
+    check_positive(name, a[1], "degrees of freedom")?;
+    Ok(match a[0] {
+        0. => Num::NEG_INFINITY,
+        1. => Num::INFINITY,
+        p => invert(p, |x| std_t_cdf(x, a[1]), -1., 1., false),
+    })
+}
+
+fn chisq_pdf(name: &str, a: &[Num]) -> Result<Num> {
+    let (x, k) = (a[0], a[1]);
+    check_positive(name, k, "degrees of freedom")?;
+    Ok(match x {
+        x if x < 0. => 0.,
+        0. if k == 2. => 0.5,
+        0. if k > 2. => 0.,
+        0. => Num::INFINITY,
+        x => ((k / 2. - 1.) * x.ln() - x / 2. - k / 2. * (2f64).ln() - lgamma(k / 2.)).exp(),
+    })
+}
+
+fn chisq_cdf(name: &str, a: &[Num]) -> Result<Num> {
+    check_positive(name, a[1], "degrees of freedom")?;
+    Ok(match a[0] {
+        x if x <= 0. => 0.,
+        x => gamma_p(a[1] / 2., x / 2.),
+    })
+}
+
+fn chisq_inv(name: &str, a: &[Num]) -> Result<Num> {
+    check_prob(name, a[0])?;
+    check_positive(name, a[1], "degrees of freedom")?;
+    Ok(match a[0] {
+        1. => Num::INFINITY,
+        p => invert(p, |x| gamma_p(a[1] / 2., x / 2.), 0., a[1], true),
+    })
+}
+
+fn exp_pdf(name: &str, a: &[Num]) -> Result<Num> {
+    check_positive(name, a[1], "rate")?;
+    Ok(match a[0] {
+        x if x < 0. => 0.,
+        x => a[1] * (-a[1] * x).exp(),
+    })
+}
+
+fn exp_cdf(name: &str, a: &[Num]) -> Result<Num> {
+    check_positive(name, a[1], "rate")?;
+    Ok(match a[0] {
+        x if x < 0. => 0.,
+        x => -(-a[1] * x).exp_m1(),
+    })
+}
+
+fn exp_inv(name: &str, a: &[Num]) -> Result<Num> {
+    check_prob(name, a[0])?;
+    check_positive(name, a[1], "rate")?;
+    Ok(-(-a[0]).ln_1p() / a[1])
+}
+
+fn check_binom(name: &str, n: Num, p: Num) -> Result<()> {
+    check(
+        name,
+        n >= 0. && n.fract() == 0.,
+        "trials must be a non-negative integer",
+    )?;
+    check_prob(name, p)
+}
+
+fn binom_pmf(name: &str, a: &[Num]) -> Result<Num> {
+    let (k, n, p) = (a[0], a[1], a[2]);
+    check_binom(name, n, p)?;
+    if k < 0. || k > n || k.fract() != 0. {
+        return Ok(0.);
+    }
+
+    // Avoid ln(0) for the edge probabilities
+    Ok(match p {
+        0. => (k == 0.) as u8 as Num,
+        1. => (k == n) as u8 as Num,
+        p => (lgamma(n + 1.) - lgamma(k + 1.) - lgamma(n - k + 1.)
+            + k * p.ln()
+            + (n - k) * (-p).ln_1p())
+        .exp(),
+    })
+}
+
+fn binom_cdf(name: &str, a: &[Num]) -> Result<Num> {
+    let (k, n, p) = (a[0].floor(), a[1], a[2]);
+    check_binom(name, n, p)?;
+    Ok(std_binom_cdf(k, n, p))
+}
+
+fn std_binom_cdf(k: Num, n: Num, p: Num) -> Num {
+    match k {
+        k if k < 0. => 0.,
+        k if k >= n => 1.,
+        k => beta_inc(1. - p, n - k, k + 1.),
+    }
+}
+
+fn binom_inv(name: &str, a: &[Num]) -> Result<Num> {
+    let (q, n, p) = (a[0], a[1], a[2]);
+    check_prob(name, q)?;
+    check_binom(name, n, p)?;
+    Ok(invert_discrete(q, |k| std_binom_cdf(k, n, p), 0., Some(n)))
+}
+
+fn poiss_pmf(name: &str, a: &[Num]) -> Result<Num> {
+    let (k, l) = (a[0], a[1]);
+    check_positive(name, l, "rate")?;
+    if k < 0. || k.fract() != 0. {
+        return Ok(0.);
+    }
+
+    Ok((k * l.ln() - l - lgamma(k + 1.)).exp())
+}
+
+fn poiss_cdf(name: &str, a: &[Num]) -> Result<Num> {
+    let (k, l) = (a[0].floor(), a[1]);
+    check_positive(name, l, "rate")?;
+    Ok(std_poiss_cdf(k, l))
+}
+
+fn std_poiss_cdf(k: Num, l: Num) -> Num {
+    match k {
+        k if k < 0. => 0.,
+        k => gamma_q(k + 1., l),
+    }
+}
+
+fn poiss_inv(name: &str, a: &[Num]) -> Result<Num> {
+    let (q, l) = (a[0], a[1]);
+    check_prob(name, q)?;
+    check_positive(name, l, "rate")?;
+    Ok(match q {
+        1. => Num::INFINITY,
+        q => invert_discrete(q, |k| std_poiss_cdf(k, l), 0., None),
+    })
+}
+
+fn check_unif(name: &str, a: Num, b: Num) -> Result<()> {
+    check(name, a < b, "lower bound must be less than the upper bound")
+}
+
+fn unif_pdf(name: &str, a: &[Num]) -> Result<Num> {
+    check_unif(name, a[1], a[2])?;
+    Ok(match a[0] {
+        x if x < a[1] || x > a[2] => 0.,
+        _ => 1. / (a[2] - a[1]),
+    })
+}
+
+fn unif_cdf(name: &str, a: &[Num]) -> Result<Num> {
+    check_unif(name, a[1], a[2])?;
+    Ok(((a[0] - a[1]) / (a[2] - a[1])).clamp(0., 1.))
+}
+
+fn unif_inv(name: &str, a: &[Num]) -> Result<Num> {
+    check_prob(name, a[0])?;
+    check_unif(name, a[1], a[2])?;
+    Ok(a[1] + a[0] * (a[2] - a[1]))
+}
+
+fn check_geom(name: &str, p: Num) -> Result<()> {
+    check(name, p > 0. && p <= 1., "probability must be in (0, 1]")
+}
+
+fn geom_pmf(name: &str, a: &[Num]) -> Result<Num> {
+    let (k, p) = (a[0], a[1]);
+    check_geom(name, p)?;
+    if k < 1. || k.fract() != 0. {
+        return Ok(0.);
+    }
+
+    Ok((-p).ln_1p().mul_add(k - 1., p.ln()).exp())
+}
+
+fn geom_cdf(name: &str, a: &[Num]) -> Result<Num> {
+    let (k, p) = (a[0].floor(), a[1]);
+    check_geom(name, p)?;
+    if k < 1. {
+        return Ok(0.);
+    }
+
+    Ok(-((-p).ln_1p() * k).exp_m1())
+}
+
+fn geom_inv(name: &str, a: &[Num]) -> Result<Num> {
+    let (q, p) = (a[0], a[1]);
+    check_prob(name, q)?;
+    check_geom(name, p)?;
+    Ok(match (q, p) {
+        (0., _) | (_, 1.) => 1.,
+        (1., _) => Num::INFINITY,
+        (q, p) => {
+            // Step back if rounding pushed us past the boundary
+            let k = ((-q).ln_1p() / (-p).ln_1p()).ceil().max(1.);
+            match k > 1. && -((-p).ln_1p() * (k - 1.)).exp_m1() >= q {
+                true => k - 1.,
+                false => k,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Reference values are rounded to the nearest f64, from closed forms where there is one
+    fn assert_rel(actual: Result<Num>, expected: Num, tolerance: Num) {
+        let actual = actual.unwrap();
+        let error = ((actual - expected) / expected).abs();
+        assert!(
+            error <= tolerance,
+            "got {actual}, expected {expected}, relative error {error:e}"
+        );
+    }
+
+    #[test]
+    fn test_norm_inv() {
+        assert_rel(norm_inv("", &[0.975, 0., 1.]), 1.959_963_984_540_054, 1e-12);
+        assert_rel(norm_inv("", &[0.3, 10., 2.]), 8.951_198_974_583_919, 1e-12);
+        assert_rel(
+            norm_inv("", &[1e-10, 0., 1.]),
+            -6.361_340_902_404_056,
+            1e-12,
+        );
+        assert!(norm_inv("", &[1.5, 0., 1.]).is_err());
+    }
+
+    #[test]
+    fn test_t_inv() {
+        assert_rel(t_inv("", &[0.975, 10.]), 2.228_138_851_986_274, 1e-12);
+        // With one degree of freedom the t distribution is Cauchy, whose quantile is tan
+        assert_rel(t_inv("", &[0.95, 1.]), (0.45 * PI).tan(), 1e-12);
+        for p in [0.01, 0.4, 0.9] {
+            assert_rel(t_cdf("", &[t_inv("", &[p, 3.5]).unwrap(), 3.5]), p, 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_chisq_inv() {
+        // The square of a standard normal, and an exponential with mean 2
+        assert_rel(
+            chisq_inv("", &[0.95, 1.]),
+            1.959_963_984_540_054_f64.powi(2),
+            1e-12,
+        );
+        assert_rel(chisq_inv("", &[0.5, 2.]), 2. * 2_f64.ln(), 1e-12);
+    }
+
+    #[test]
+    fn test_discrete_inv() {
+        assert_eq!(binom_inv("", &[0.5, 10., 0.5]).unwrap(), 5.);
+        // A cdf value exactly is reached at that k, not the next one
+        let at_3 = binom_cdf("", &[3., 10., 0.5]).unwrap();
+        assert_eq!(binom_inv("", &[at_3, 10., 0.5]).unwrap(), 3.);
+        assert_eq!(binom_inv("", &[1., 10., 0.5]).unwrap(), 10.);
+
+        assert_eq!(poiss_inv("", &[0.5, 3.]).unwrap(), 3.);
+        let at_2 = poiss_cdf("", &[2., 3.]).unwrap();
+        assert_eq!(poiss_inv("", &[at_2, 3.]).unwrap(), 2.);
+        assert_eq!(poiss_inv("", &[0., 3.]).unwrap(), 0.);
+    }
+}
@@ -0,0 +1,434 @@
+//! Symbolic differentiation, simplification and Taylor expansion.
+
+use std::f64::consts::{LN_10, LN_2, PI};
+
+use super::{calculus::limit, reqire_args, reqire_args_range, var_arg, Function};
+use crate::calc::{
+    angle::AngleMode,
+    simplify::{is_constant, simplify, sum_of, tree},
+    solver::{func_args, Context},
+    Error, Num, Ops, Result, Token,
+};
+
+// Taylor coefficients this much smaller than the largest one are dropped
+const TAYLOR_EPSILON: Num = 1e-14;
+// Most leading zero coefficients the bottom of a quotient can have
+const MAX_ZEROS: usize = 4;
+
+pub struct Simplify;
+impl Function for Simplify {
+    fn name(&self) -> &'static str {
+        "simplify"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn is_symbolic(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        let expr = self.call_value(args, context)?;
+        context.evaluate(expr)
+    }
+
+    // (expr)
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args(self.name(), &args, 1)?;
+        let expr = expression(&args[0], context)?;
+        Ok(simplify(expr, context))
+    }
+}
+
+pub struct Diff;
+impl Function for Diff {
+    fn name(&self) -> &'static str {
+        "diff"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn is_symbolic(&self) -> bool {
+        true
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    // Evaluates the derivative with the current variables
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        let derivative = self.call_value(args, context)?;
+        context.evaluate(derivative)
+    }
+
+    // (expr, var, [order])
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args_range(self.name(), &args, 2, 3)?;
+        let var = var_arg(self.name(), &args[1])?;
+        let order = match args.get(2) {
+            Some(i) => context.evaluate(i.to_owned())?,
+            None => 1.,
+        };
+        if order < 0. || order.fract() != 0. {
+            return Err(Error::InvalidArgument(
+                self.name().to_owned(),
+                "the order must be a non-negative integer".to_owned(),
+            ));
+        }
+
+        let mut expr = expression(&args[0], context)?;
+        for _ in 0..order as usize {
+            let derivative = differentiate(&expr, &var, context)?;
+            expr = simplify(derivative, context);
+        }
+
+        Ok(expr)
+    }
+}
+
+pub struct Taylor;
+impl Function for Taylor {
+    fn name(&self) -> &'static str {
+        "taylor"
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn is_symbolic(&self) -> bool {
+        true
+    }
+
+    fn takes_names(&self) -> bool {
+        true
+    }
+
+    fn call(&self, args: Vec<Token>, context: &mut Context) -> Result<Num> {
+        let polynomial = self.call_value(args, context)?;
+        context.evaluate(polynomial)
+    }
+
+    // (expr, var, a, n)
+    fn call_value(&self, args: Vec<Token>, context: &mut Context) -> Result<Token> {
+        reqire_args(self.name(), &args, 4)?;
+        let var = var_arg(self.name(), &args[1])?;
+        let a = context.evaluate(args[2].to_owned())?;
+        let n = context.evaluate(args[3].to_owned())?;
+        if !a.is_finite() || n < 0. || n.fract() != 0. {
+            return Err(Error::InvalidArgument(
+                self.name().to_owned(),
+                "expected a finite point and a non-negative integer order".to_owned(),
+            ));
+        }
+
+        let expr = expression(&args[0], context)?;
+        let coeffs = coefficients(expr, &var, a, n as usize, context)?;
+
+        // Keep points like `pi` as they were written
+        let point = simplify(args[2].to_owned(), context);
+        let base = match a {
+            0. => Token::Var(var),
+            _ if is_constant(&point, context) => tree(Ops::Sub, Token::Var(var), point),
+            a => tree(Ops::Sub, Token::Var(var), Token::Number(a)),
+        };
+
+        // Drop rounding error, like the cos(pi / 2) in the expansion of sin(x) at pi
+        let scale = coeffs.iter().fold(0., |a: Num, x| a.max(x.abs()));
+        let terms = coeffs.into_iter().enumerate().map(|(k, coeff)| {
+            let power = match k {
+                0 => Token::Number(1.),
+                1 => base.to_owned(),
+                k => tree(Ops::Pow, base.to_owned(), Token::Number(k as Num)),
+            };
+            (
+                power,
+                if coeff.abs() <= TAYLOR_EPSILON * scale {
+                    0.
+                } else {
+                    coeff
+                },
+            )
+        });
+
+        Ok(sum_of(terms))
+    }
+}
+
+// Taylor coefficients f^(k)(a) / k! up to k = n. Quotients that can not be evaluated at `a`,
+// like sin(x) / x at 0, divide the series of their top and bottom.
+fn coefficients(
+    expr: Token,
+    var: &str,
+    a: Num,
+    n: usize,
+    context: &mut Context,
+) -> Result<Vec<Num>> {
+    if let Token::Tree(Ops::Div, top, bottom) = &expr {
+        if !context.evaluate_with(&expr, var, a)?.is_finite() {
+            if let Some(coeffs) = quotient(top, bottom, var, a, n, context)? {
+                return Ok(coeffs);
+            }
+        }
+    }
+
+    let mut expr = expr;
+    let mut coeffs = Vec::new();
+    let mut factorial = 1.;
+    for k in 0..=n {
+        if k > 0 {
+            let derivative = differentiate(&expr, var, context)?;
+            expr = simplify(derivative, context);
+            factorial *= k as Num;
+        }
+
+        let value = match context.evaluate_with(&expr, var, a)? {
+            i if i.is_finite() => i,
+            _ => limit(&expr, var, a, 0., context)?,
+        };
+        coeffs.push(value / factorial);
+    }
+
+    Ok(coeffs)
+}
+
+// Series of top / bottom where the bottom starts with m zero coefficients, which the top has
+// to share for the singularity to be removable
+fn quotient(
+    top: &Token,
+    bottom: &Token,
+    var: &str,
+    a: Num,
+    n: usize,
+    context: &mut Context,
+) -> Result<Option<Vec<Num>>> {
+    let bottom = coefficients(bottom.to_owned(), var, a, n + MAX_ZEROS, context)?;
+    let is_zero = |x: &[Num], i: usize| {
+        let scale = x.iter().fold(0., |a: Num, x| a.max(x.abs()));
+        x[i].abs() <= TAYLOR_EPSILON * scale
+    };
+    let m = match (0..bottom.len()).find(|i| !is_zero(&bottom, *i)) {
+        Some(m) => m,
+        None => return Ok(None),
+    };
+
+    let top = coefficients(top.to_owned(), var, a, n + m, context)?;
+    if (0..m).any(|i| !is_zero(&top, i)) {
+        return Ok(None);
+    }
+
+    let (top, bottom) = (&top[m..], &bottom[m..]);
+    let mut out = Vec::<Num>::new();
+    for k in 0..=n {
+        let known = (1..=k).map(|j| bottom[j] * out[k - j]).sum::<Num>();
+        out.push((top[k] - known) / bottom[0]);
+    }
+
+    Ok(Some(out))
+}
+
+/// Differentiates `expr` with respect to `var`, without simplifying the result.
+/// Variables holding expressions are substituted, other variables are constants.
+pub fn differentiate(expr: &Token, var: &str, context: &mut Context) -> Result<Token> {
+    Ok(match expr {
+        Token::Number(_) => Token::Number(0.),
+        Token::Var(name) if name.to_lowercase() == var => Token::Number(1.),
+        Token::Var(name) => match context.get_var(name).cloned() {
+            Some(i @ Token::Tree(..)) => differentiate(&i, var, context)?,
+            _ => Token::Number(0.),
+        },
+        Token::List(items) => Token::List(
+            items
+                .iter()
+                .map(|x| differentiate(x, var, context))
+                .collect::<Result<_>>()?,
+        ),
+        Token::Tree(op, u, v) => {
+            let du = differentiate(u, var, context)?;
+            let dv = differentiate(v, var, context)?;
+            let (u, v) = (*u.to_owned(), *v.to_owned());
+            match op {
+                Ops::Add | Ops::Sub | Ops::Equal => tree(*op, du, dv),
+                Ops::Mul => tree(Ops::Add, tree(Ops::Mul, du, v), tree(Ops::Mul, u, dv)),
+                Ops::Div => tree(
+                    Ops::Div,
+                    tree(
+                        Ops::Sub,
+                        tree(Ops::Mul, du, v.to_owned()),
+                        tree(Ops::Mul, u, dv),
+                    ),
+                    tree(Ops::Pow, v, Token::Number(2.)),
+                ),
+                Ops::Pow => power(u, v, du, dv, var, context),
+                // u % v = u - v * floor(u / v), ignoring the jumps
+                Ops::Mod => tree(
+                    Ops::Sub,
+                    du,
+                    tree(Ops::Mul, dv, func("floor", tree(Ops::Div, u, v))),
+                ),
+            }
+        }
+        Token::Func(name, args) => {
+            if is_symbolic(name, context) {
+                let inner = expression(expr, context)?;
+                return differentiate(&inner, var, context);
+            }
+
+            let name = name.to_lowercase();
+            let args = func_args(args.to_owned());
+            if !args.iter().any(|x| depends_on(x, var, context)) {
+                return Ok(Token::Number(0.));
+            }
+
+            if args.len() != 1 {
+                return Err(no_rule(&name));
+            }
+
+            let u = args[0].to_owned();
+            let du = differentiate(&u, var, context)?;
+            tree(Ops::Mul, outer_derivative(&name, u, context.angle())?, du)
+        }
+        Token::Dice(_) => {
+            return Err(Error::InvalidArgument(
+                "diff".to_owned(),
+                "dice can not be differentiated".to_owned(),
+            ))
+        }
+        _ => return Err(Error::InvalidExpression),
+    })
+}
+
+// d/dx u^v, using the simpler rules when the base or exponent is constant
+fn power(u: Token, v: Token, du: Token, dv: Token, var: &str, context: &Context) -> Token {
+    let one = Token::Number(1.);
+    if !depends_on(&v, var, context) {
+        let exponent = tree(Ops::Sub, v.to_owned(), one);
+        return tree(Ops::Mul, tree(Ops::Mul, v, tree(Ops::Pow, u, exponent)), du);
+    }
+
+    let pow = tree(Ops::Pow, u.to_owned(), v.to_owned());
+    if !depends_on(&u, var, context) {
+        return tree(Ops::Mul, tree(Ops::Mul, pow, func("ln", u)), dv);
+    }
+
+    // u^v * (v' * ln(u) + v * u' / u)
+    tree(
+        Ops::Mul,
+        pow,
+        tree(
+            Ops::Add,
+            tree(Ops::Mul, dv, func("ln", u.to_owned())),
+            tree(Ops::Div, tree(Ops::Mul, v, du), u),
+        ),
+    )
+}
+
+// Derivative of a single argument function at `u`, to be multiplied by u'. Outside of
+// radians, trigonometric functions pick up the size of the angle unit from the chain rule.
+fn outer_derivative(name: &str, u: Token, angle: AngleMode) -> Result<Token> {
+    let num = Token::Number;
+    let recip = |x| tree(Ops::Div, num(1.), x);
+    let square = |x| tree(Ops::Pow, x, num(2.));
+    let unit = angle.to_radians(1.);
+    let per_unit = |x| match unit {
+        1. => x,
+        _ => tree(Ops::Mul, x, num(unit)),
+    };
+    let per_radian = |x| match unit {
+        1. => x,
+        _ => tree(Ops::Div, x, num(unit)),
+    };
+
+    Ok(match name {
+        "floor" | "ceil" | "round" | "trunc" | "sig_num" => num(0.),
+        "fract" => num(1.),
+        "abs" => func("sig_num", u),
+        "exp" | "exp_m_1" => func("exp", u),
+        "exp_2" => tree(Ops::Mul, func("exp_2", u), num(LN_2)),
+        "ln" => recip(u),
+        "log_2" => recip(tree(Ops::Mul, u, num(LN_2))),
+        "log_10" => recip(tree(Ops::Mul, u, num(LN_10))),
+        "ln_1_p" => recip(tree(Ops::Add, num(1.), u)),
+        "sqrt" => recip(tree(Ops::Mul, num(2.), func("sqrt", u))),
+        "cbrt" => recip(tree(Ops::Mul, num(3.), square(func("cbrt", u)))),
+        "sin" => per_unit(func("cos", u)),
+        "cos" => per_unit(tree(Ops::Mul, num(-1.), func("sin", u))),
+        "tan" => per_unit(recip(square(func("cos", u)))),
+        "asin" => per_radian(recip(func("sqrt", tree(Ops::Sub, num(1.), square(u))))),
+        "acos" => per_radian(tree(
+            Ops::Div,
+            num(-1.),
+            func("sqrt", tree(Ops::Sub, num(1.), square(u))),
+        )),
+        "atan" => per_radian(recip(tree(Ops::Add, num(1.), square(u)))),
+        "sinh" => func("cosh", u),
+        "cosh" => func("sinh", u),
+        "tanh" => recip(square(func("cosh", u))),
+        "asinh" => recip(func("sqrt", tree(Ops::Add, square(u), num(1.)))),
+        "acosh" => recip(func("sqrt", tree(Ops::Sub, square(u), num(1.)))),
+        "atanh" => recip(tree(Ops::Sub, num(1.), square(u))),
+        "recip" => tree(Ops::Div, num(-1.), square(u)),
+        "to_degrees" => num(180. / PI),
+        "to_radians" => num(PI / 180.),
+        "deg" => num(angle.convert_degrees(1.)),
+        _ => return Err(no_rule(name)),
+    })
+}
+
+fn no_rule(name: &str) -> Error {
+    Error::InvalidArgument(
+        "diff".to_owned(),
+        format!("no derivative rule for `{}`", name),
+    )
+}
+
+/// Gets the expression an argument stands for, expanding variables that hold expressions (like
+/// `ans`) and calls to symbolic functions (like `diff`).
+pub fn expression(arg: &Token, context: &mut Context) -> Result<Token> {
+    Ok(match arg {
+        Token::Var(name) => match context.get_var(name) {
+            Some(i @ Token::Tree(..)) => i.to_owned(),
+            _ => arg.to_owned(),
+        },
+        Token::Func(name, _) if is_symbolic(name, context) => {
+            context.evaluate_value(arg.to_owned())?
+        }
+        _ => arg.to_owned(),
+    })
+}
+
+fn is_symbolic(name: &str, context: &Context) -> bool {
+    context
+        .funcs
+        .get(&name.to_lowercase())
+        .map(|x| x.is_symbolic())
+        .unwrap_or(false)
+}
+
+/// Whether `expr` changes with `var`, following variables that hold expressions.
+pub fn depends_on(expr: &Token, var: &str, context: &Context) -> bool {
+    match expr {
+        Token::Var(name) if name.to_lowercase() == var => true,
+        Token::Var(name) => match context.get_var(name) {
+            Some(i @ Token::Tree(..)) => depends_on(i, var, context),
+            _ => false,
+        },
+        Token::Tree(_, left, right) => {
+            depends_on(left, var, context) || depends_on(right, var, context)
+        }
+        Token::Func(_, args) => args.iter().flatten().any(|x| depends_on(x, var, context)),
+        Token::List(items) | Token::Group(items) => {
+            items.iter().any(|x| depends_on(x, var, context))
+        }
+        _ => false,
+    }
+}
+
+fn func(name: &str, arg: Token) -> Token {
+    Token::Func(name.to_owned(), vec![vec![arg]])
+}
@@ -1,54 +1,336 @@
 // #![feature(result_option_inspect)]
 
-use std::{
-    env,
-    io::{stdin, stdout, Write},
-};
+use std::{env, fs, process};
 
-use calc::{solver::Context, tokens::tokenize, tree::create_tree};
+use calc::{
+    angle::AngleMode,
+    format::Notation,
+    sequence,
+    solver::Context,
+    tokens::{tokenize, tokenize_statements},
+    tree::create_tree,
+    Error, Ops, Result, Token,
+};
 use colored::Colorize;
-
-use crate::calc::Token;
+use rustyline::error::ReadlineError;
 
 mod calc;
+mod editor;
 mod funcs;
 
+// Most digits `fixed` and `sig` can be set to, f64s only hold about 17
+const MAX_DIGITS: usize = 20;
+
+const COMMANDS: &[&str] = &[
+    ":vars             variables and sequences with their values",
+    ":funcs            names of every function",
+    ":help <function>  how to call a function",
+    ":del <name>       forgets a variable or sequence",
+    ":reset            forgets every variable and sequence",
+    ":mode [setting]   shows or changes a mode, like `deg`, `fixed 2` or `sep`",
+    ":save <file>      writes variables and sequences to a file",
+    ":load <file>      reads them back in",
+    ":quit             exits",
+];
+
 fn main() {
     let args = env::args().collect::<Vec<_>>();
     let mut context = Context::new();
+    context.show_rolls = args.contains(&"--rolls".to_string());
+    context.simplify = args.contains(&"--simplify".to_string());
+
+    // Any other flag is a mode, like `--deg` or `--fixed=2`
+    for i in args.iter().filter_map(|x| x.strip_prefix("--")) {
+        let (setting, value) = match i.split_once('=') {
+            Some((setting, value)) => (setting, Some(value)),
+            None => (i, None),
+        };
+        if ["rolls", "simplify", "quiet", "vi"].contains(&setting) {
+            continue;
+        }
+        if let Err(e) = set_mode(setting, value, &mut context) {
+            println!("{}", format!("[ERROR] {}", e).red());
+        }
+    }
 
-    if let Some(i) = args.get(1) {
+    if let Some(i) = args.iter().skip(1).find(|x| !x.starts_with("--")) {
         let quiet = args.contains(&"--quiet".to_string());
 
-        let result = tokenize(i)
-            .and_then(create_tree)
-            .and_then(|x| context.evaluate(x));
-        match result {
-            Ok(i) => println!("{}{i}", if quiet { "" } else { " ⮩ " }),
+        match run(i, &mut context) {
+            Ok(i) => println!(
+                "{}{}",
+                if quiet { "" } else { " ⮩ " },
+                context.format.token(&i)
+            ),
             Err(e) if !quiet => println!("{}", format!("[ERROR] {}", e).red()),
             _ => {}
         }
     }
 
+    let mut editor = editor::editor(args.contains(&"--vi".to_string())).unwrap();
+    let history = editor::history_path();
+    if let Some(path) = &history {
+        // There is no history yet on the first run
+        let _ = editor.load_history(path);
+    }
+
     loop {
-        let mut input = String::new();
-        print!(" ▷ ");
-        stdout().flush().unwrap();
-        stdin().read_line(&mut input).unwrap();
-        let result = tokenize(&input)
-            // .inspect(|x| println!("TOKENIZE {:?}", x))
-            .and_then(create_tree)
-            // .inspect(|x| println!("TREE {:?}", x))
-            .and_then(|x| context.evaluate(x));
+        editor.helper_mut().unwrap().update(&context);
+        let prompt = format!(" {} ▷ ", context.angle().to_string().dimmed());
+        let input = match editor.readline(&prompt) {
+            Ok(i) => i,
+            // Ctrl-C drops the line, Ctrl-D leaves
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                println!("{}", format!("[ERROR] {}", e).red());
+                break;
+            }
+        };
+
+        // Saved after every line, as `exit()` and `:quit` leave straight away
+        if let Some(path) = &history {
+            if let Err(e) = editor.save_history(path) {
+                println!("{}", format!("[ERROR] Could not save history: {}", e).red());
+            }
+        }
+
+        if input.trim().is_empty() {
+            continue;
+        }
 
-        if let Ok(i) = result {
-            // Maybe use prevous tree to get an exact result
-            context.set_var("ans", Token::Number(i));
+        if let Some(i) = input.trim().strip_prefix(':') {
+            match command(i, &mut context) {
+                Ok(i) => println!(" ⮩ {i}"),
+                Err(e) => println!("{}", format!(" ⮩ {}", e).red()),
+            }
+            continue;
+        }
+
+        let result = run(&input, &mut context);
+
+        // Definitions are not values
+        match &result {
+            Ok(i) if !is_definition(i) => {
+                // Maybe use prevous tree to get an exact result
+                context.set_var("ans", i.to_owned());
+            }
+            _ => {}
         }
 
         match result {
-            Ok(i) => println!(" ⮩ {i}"),
+            Ok(i) => println!(" ⮩ {}", context.format.token(&i)),
             Err(e) => println!("{}", format!(" ⮩ {}", e).red()),
         }
     }
 }
+
+// Runs every comma separated statement in the input, giving the result of the last one
+fn run(input: &str, context: &mut Context) -> Result<Token> {
+    let mut result = Err(Error::InvalidExpression);
+    for i in tokenize_statements(input)? {
+        let tree = context.optimize(create_tree(i)?);
+        result = Ok(context.run(tree)?);
+    }
+
+    result
+}
+
+// Commands like `:mode deg` change settings or show what is defined rather than evaluating
+// anything
+fn command(input: &str, context: &mut Context) -> std::result::Result<String, String> {
+    let mut parts = input.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("vars"), None) => Ok(vars(context)),
+        (Some("funcs"), None) => {
+            let mut names = context
+                .funcs
+                .keys()
+                .chain(context.sequences.keys())
+                .map(|x| x.as_str())
+                .collect::<Vec<_>>();
+            names.sort_unstable();
+            Ok(names.join(", "))
+        }
+        (Some("help"), None) => Ok(COMMANDS.join("\n    ")),
+        (Some("help"), Some(name)) => match context.sequences.get(&name.to_lowercase()) {
+            Some(_) => Ok(format!("{} is a sequence you defined", name)),
+            None => funcs::help(&name.to_lowercase())
+                .map(|x| x.to_owned())
+                .ok_or(format!("Unknown function `{}`", name)),
+        },
+        (Some("del"), Some(name)) => {
+            let name = name.to_lowercase();
+            let found =
+                context.vars.remove(&name).is_some() | context.sequences.remove(&name).is_some();
+            sequence::clear_memos(context);
+            match found {
+                true => Ok(format!("Deleted `{}`", name)),
+                false => Err(format!("Nothing called `{}` is defined", name)),
+            }
+        }
+        (Some("reset"), None) => {
+            reset(context);
+            Ok("Cleared every variable and definition".to_owned())
+        }
+        (Some("mode"), None) => Ok(modes(context)),
+        (Some("mode"), Some(setting)) => {
+            set_mode(setting, parts.next(), context)?;
+            Ok(modes(context))
+        }
+        (Some("save"), Some(path)) => {
+            fs::write(path, save(context))
+                .map_err(|e| format!("Could not write `{}`: {}", path, e))?;
+            Ok(format!("Saved to `{}`", path))
+        }
+        (Some("load"), Some(path)) => {
+            let file = fs::read_to_string(path)
+                .map_err(|e| format!("Could not read `{}`: {}", path, e))?;
+            load(&file, context).map_err(|e| e.to_string())?;
+            Ok(format!("Loaded `{}`", path))
+        }
+        (Some("quit" | "q"), None) => process::exit(0),
+        _ => Err(format!("Unknown command `:{}`, try `:help`", input)),
+    }
+}
+
+// Every variable, constants included, then every sequence
+fn vars(context: &Context) -> String {
+    let mut vars = context
+        .vars
+        .iter()
+        .map(|(name, value)| format!("{} = {}", name, context.format.token(value)))
+        .collect::<Vec<_>>();
+    vars.sort_unstable();
+    vars.extend(sequence_lines(context));
+    vars.join("\n    ")
+}
+
+// The rule and given values of every sequence, as the definitions that made them
+fn sequence_lines(context: &Context) -> Vec<String> {
+    let mut names = context.sequences.keys().collect::<Vec<_>>();
+    names.sort_unstable();
+
+    let mut out = Vec::new();
+    for name in names {
+        let sequence = &context.sequences[name];
+        if let Some(rule) = &sequence.rule {
+            out.push(format!("{}({}) = {}", name, sequence.var, rule));
+        }
+        out.extend(
+            sequence
+                .values
+                .iter()
+                .map(|(index, value)| format!("{}({}) = {}", name, index, value)),
+        );
+    }
+
+    out
+}
+
+// Variables other than the constants and every sequence, one `name = value` per line, with
+// numbers written in full so nothing is lost
+fn save(context: &Context) -> String {
+    let mut lines = context
+        .vars
+        .iter()
+        .filter(|(name, _)| !context.is_constant(name))
+        .map(|(name, value)| format!("{} = {}", name, saved_value(value)))
+        .collect::<Vec<_>>();
+    lines.sort_unstable();
+    lines.extend(sequence_lines(context));
+    lines.iter().map(|x| format!("{}\n", x)).collect()
+}
+
+// Complex numbers are written as calls to `complex` so they can be read back in
+fn saved_value(token: &Token) -> String {
+    match token {
+        Token::Complex(i) => format!("complex({}, {})", i.re, i.im),
+        Token::List(items) => format!(
+            "[{}]",
+            items.iter().map(saved_value).collect::<Vec<_>>().join(", ")
+        ),
+        i => i.to_string(),
+    }
+}
+
+// Runs the lines of a saved file. Lines like `x = 2` set a variable, which is not something
+// expressions can do.
+fn load(file: &str, context: &mut Context) -> Result<()> {
+    for line in file.lines().filter(|x| !x.trim().is_empty()) {
+        match create_tree(tokenize(line)?)? {
+            Token::Tree(Ops::Equal, left, right) if matches!(*left, Token::Var(_)) => {
+                let Token::Var(name) = *left else {
+                    unreachable!()
+                };
+                // Expressions, like the results of `diff`, are kept as they are
+                let value = match *right {
+                    i @ Token::Tree(..) => i,
+                    i => context.evaluate_value(i)?,
+                };
+                context.set_var(&name.to_lowercase(), value);
+            }
+            tree => {
+                context.run(tree)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Forgets every variable and definition, keeping the modes and flags
+fn reset(context: &mut Context) {
+    let mut fresh = Context::new();
+    fresh.show_rolls = context.show_rolls;
+    fresh.simplify = context.simplify;
+    fresh.format = context.format;
+    fresh.set_angle(context.angle());
+    *context = fresh;
+}
+
+// Changes the angle mode (`deg`), the notation results are written in (`fixed 2`) or whether
+// they get thousands separators (`sep` / `nosep`)
+fn set_mode(
+    setting: &str,
+    value: Option<&str>,
+    context: &mut Context,
+) -> std::result::Result<(), String> {
+    if let Some(angle) = AngleMode::parse(setting) {
+        context.set_angle(angle);
+        return Ok(());
+    }
+
+    let digits = |min: usize| {
+        value
+            .and_then(|x| x.parse::<usize>().ok())
+            .filter(|x| (min..=MAX_DIGITS).contains(x))
+            .ok_or(format!(
+                "`{}` needs a number of digits from {} to {}",
+                setting, min, MAX_DIGITS
+            ))
+    };
+
+    let format = &mut context.format;
+    match setting {
+        "smart" => format.notation = Notation::Smart,
+        "fixed" => format.notation = Notation::Fixed(digits(0)?),
+        "sig" => format.notation = Notation::Sig(digits(1)?),
+        "sci" => format.notation = Notation::Sci,
+        "eng" => format.notation = Notation::Eng,
+        "si" => format.notation = Notation::Si,
+        "frac" => format.notation = Notation::Frac,
+        "sep" => format.separators = true,
+        "nosep" => format.separators = false,
+        _ => return Err(format!("Unknown mode `{}`", setting)),
+    }
+    Ok(())
+}
+
+fn modes(context: &Context) -> String {
+    format!("{}, {}", context.angle(), context.format)
+}
+
+fn is_definition(token: &Token) -> bool {
+    matches!(token, Token::Tree(Ops::Equal, ..))
+}